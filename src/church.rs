@@ -0,0 +1,85 @@
+use crate::{Expr, Expr::*};
+
+// Largest numeral accepted by the parser, terms much deeper than
+// this overflow the stack when printed or reduced
+pub const MAX_NUMERAL: usize = 1000;
+
+// Church numeral n
+//
+// 3
+// \f.\x.f (f (f x))
+pub fn numeral(n: usize) -> Expr {
+    let mut e = Expr::var("x");
+    for _ in 0..n {
+        e = Expr::app(Expr::var("f"), e);
+    }
+    Expr::lam("f", Expr::lam("x", e))
+}
+
+// Church boolean b
+//
+// true    false
+// \t.\f.t \t.\f.f
+pub fn boolean(b: bool) -> Expr {
+    Expr::lam("t", Expr::lam("f", Expr::var(if b { "t" } else { "f" })))
+}
+
+pub fn read_numeral(e: &Expr) -> Option<usize> {
+    if let Lam(f, e) = e {
+        if let Lam(x, e) = &**e {
+            if f == x {
+                return None;
+            }
+
+            let mut n = 0;
+            let mut e = &**e;
+            while let App(g, e1) = e {
+                match &**g {
+                    Var(g) if g == f => {
+                        n += 1;
+                        e = e1;
+                    }
+                    _ => return None,
+                }
+            }
+
+            return match e {
+                Var(y) if y == x => Some(n),
+                _ => None,
+            };
+        }
+    }
+    None
+}
+
+pub fn read_boolean(e: &Expr) -> Option<bool> {
+    if let Lam(t, e) = e {
+        if let Lam(f, e) = &**e {
+            if let Var(y) = &**e {
+                if t != f {
+                    if y == t {
+                        return Some(true);
+                    } else if y == f {
+                        return Some(false);
+                    }
+                }
+            }
+        }
+    }
+    None
+}
+
+// Literal denoted by a normal form
+//
+// \f.\x.f (f x)
+// 2
+//
+// \x.\y.y is both 0 and false
+pub fn literal(e: &Expr) -> Option<String> {
+    match (read_numeral(e), read_boolean(e)) {
+        (Some(n), Some(b)) => Some(format!("{} ({})", n, b)),
+        (Some(n), None) => Some(n.to_string()),
+        (None, Some(b)) => Some(b.to_string()),
+        (None, None) => None,
+    }
+}
@@ -1,264 +1,1237 @@
-use crate::{parse, split, split::Match, Config};
-use std::fs::File;
+use crate::{
+    debruijn::Term, expr::Strategy, interrupt, parse, prelude, split, Config, Error, Expr, Subst,
+    Trace,
+};
+use std::env;
+use std::fmt;
+use std::fs::{self, File};
 use std::io::{self, BufRead};
 use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+/// Command implementation, given the registry it was found in and its
+/// parsed arguments
+pub type Fun = Rc<dyn Fn(&CommandRegistry, &mut Config, &Args) -> Result<(), Error>>;
 
-pub struct Command<'a> {
-    pub name: &'a str,
-    pub usage: &'a str,
-    pub desc: &'a str,
-    pub args: Arg,
-    pub fun: fn(&[Command], &mut Config, &str, Option<Match>),
+/// Command of the prompt, run with `:name args`
+#[derive(Clone)]
+pub struct Command {
+    pub name: String,
+    pub desc: String,
+    // Long description with examples, printed by :help name
+    pub help: String,
+    pub aliases: Vec<String>,
+    pub params: Vec<Param>,
+    fun: Fun,
 }
 
-#[derive(Eq, PartialEq)]
-pub enum Arg {
-    CheckNone,
-    CheckSome,
-    NoCheck,
+impl Command {
+    /// Command without parameters, add them with [`Command::param`]
+    pub fn new<F>(name: &str, desc: &str, fun: F) -> Self
+    where
+        F: Fn(&CommandRegistry, &mut Config, &Args) -> Result<(), Error> + 'static,
+    {
+        Command {
+            name: name.to_string(),
+            desc: desc.to_string(),
+            help: String::new(),
+            aliases: vec![],
+            params: vec![],
+            fun: Rc::new(fun),
+        }
+    }
+
+    /// Parameter following the previous ones
+    pub fn param(mut self, p: Param) -> Self {
+        self.params.push(p);
+        self
+    }
+
+    /// Other name the command can be run with
+    pub fn alias(mut self, name: &str) -> Self {
+        self.aliases.push(name.to_string());
+        self
+    }
+
+    /// Long description, printed after the synopsis by `:help name`
+    pub fn help(mut self, text: &str) -> Self {
+        self.help = text.to_string();
+        self
+    }
+
+    /// Synopsis of the command, like `:limit [num]`
+    pub fn usage(&self) -> String {
+        let mut s = format!(":{}", self.name);
+        for p in &self.params {
+            s.push_str(&format!(" {}", p));
+        }
+        s
+    }
 }
 
-const HELP_PADDING: usize = 20;
+/// What an argument is parsed into
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Kind {
+    /// Non negative integer
+    Number,
+    /// on or off, true or false
+    Bool,
+    /// Variable name, as bound by :bind
+    Name,
+    /// Any word
+    Word,
+    /// Expression spanning the rest of the line
+    Expr,
+    /// File name spanning the rest of the line
+    Path,
+    /// Rest of the line as it is
+    Text,
+}
 
-pub fn command<'a>(cmds: &[Command], cfg: &mut Config, line: &str) {
-    let mut it = split::split_n_whitespace(line, 2);
-
-    if let Some(fst) = it.next() {
-        for cmd in cmds {
-            if fst.slice == cmd.name {
-                match it.next() {
-                    Some(rest) => {
-                        if cmd.args == Arg::CheckNone {
-                            eprintln!(
-                                "Command `{}` expected no arguments, but `{}` was given.",
-                                cmd.name, rest.slice
-                            )
-                        } else {
-                            (cmd.fun)(cmds, cfg, line, Some(rest))
-                        }
-                    }
-                    None => {
-                        if cmd.args == Arg::CheckSome {
-                            eprintln!(
-                                "Command `{}` expected some arguments, but none was given.",
-                                cmd.name
-                            )
-                        } else {
-                            (cmd.fun)(cmds, cfg, line, None)
-                        }
-                    }
-                }
-                return;
-            }
+impl Kind {
+    // Whether the argument spans the rest of the line rather than a word
+    fn rest(self) -> bool {
+        matches!(self, Kind::Expr | Kind::Path | Kind::Text)
+    }
+}
+
+/// Parameter of a command
+#[derive(Debug, Clone, Copy)]
+pub struct Param {
+    pub name: &'static str,
+    pub kind: Kind,
+    pub optional: bool,
+    pub repeated: bool,
+}
+
+impl Param {
+    /// Required parameter given once
+    pub fn new(name: &'static str, kind: Kind) -> Self {
+        Param {
+            name,
+            kind,
+            optional: false,
+            repeated: false,
         }
+    }
 
-        let mut tmp = vec![];
-        for cmd in cmds {
-            if cmd.name.starts_with(fst.slice) {
-                tmp.push((cmd.usage, cmd.desc));
-            }
+    pub fn optional(mut self) -> Self {
+        self.optional = true;
+        self
+    }
+
+    /// Parameter taking every remaining word, at least one unless it is
+    /// also optional
+    pub fn repeated(mut self) -> Self {
+        self.repeated = true;
+        self
+    }
+}
+
+// name, name... or [name...]
+impl fmt::Display for Param {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let dots = if self.repeated { "..." } else { "" };
+        if self.optional {
+            write!(f, "[{}{}]", self.name, dots)
+        } else {
+            write!(f, "{}{}", self.name, dots)
         }
+    }
+}
 
-        eprintln!("Unrecognized command `{}`.", fst.slice);
-        if tmp.len() != 0 {
-            eprintln!("Similar commands:");
-            for (usage, desc) in tmp {
-                eprintln!(
-                    "    {2}{0:1$}{3}",
-                    " ",
-                    HELP_PADDING - usage.len(),
-                    usage,
-                    desc
-                )
-            }
+/// Parsed argument
+#[derive(Debug, Clone)]
+pub enum Value {
+    Number(usize),
+    Bool(bool),
+    Name(String),
+    Word(String),
+    Expr(Expr),
+    Path(PathBuf),
+    Text(String),
+}
+
+/// Arguments of a command, by parameter name
+#[derive(Debug, Clone, Default)]
+pub struct Args(Vec<(&'static str, Value)>);
+
+impl Args {
+    /// First argument given for the parameter, none if it was omitted
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.0.iter().find(|(p, _)| *p == name).map(|(_, v)| v)
+    }
+
+    /// Every argument given for a repeated parameter
+    pub fn all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Value> {
+        self.0
+            .iter()
+            .filter(move |(p, _)| *p == name)
+            .map(|(_, v)| v)
+    }
+
+    pub fn number(&self, name: &str) -> Option<usize> {
+        match self.get(name) {
+            Some(Value::Number(n)) => Some(*n),
+            _ => None,
+        }
+    }
+
+    pub fn bool(&self, name: &str) -> Option<bool> {
+        match self.get(name) {
+            Some(Value::Bool(b)) => Some(*b),
+            _ => None,
+        }
+    }
+
+    /// Name, word or text argument
+    pub fn str(&self, name: &str) -> Option<&str> {
+        self.strs(name).into_iter().next()
+    }
+
+    /// Name, word or text arguments of a repeated parameter
+    pub fn strs(&self, name: &str) -> Vec<&str> {
+        self.0
+            .iter()
+            .filter_map(|(p, v)| match v {
+                Value::Name(s) | Value::Word(s) | Value::Text(s) if *p == name => Some(s.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn expr(&self, name: &str) -> Option<&Expr> {
+        match self.get(name) {
+            Some(Value::Expr(e)) => Some(e),
+            _ => None,
+        }
+    }
+
+    pub fn path(&self, name: &str) -> Option<&Path> {
+        match self.get(name) {
+            Some(Value::Path(p)) => Some(p),
+            _ => None,
         }
-    } else {
-        help_command(cmds, cfg, "", None)
     }
 }
 
-pub const LIMIT_COMMAND: Command<'static> = Command {
-    name: "limit",
-    usage: ":limit [num]",
-    desc: "Manipulate the limit of reduction steps",
-    args: Arg::NoCheck,
-    fun: limit_command,
-};
+// Parse the arguments of cmd, starting at pos in line
+//
+// The whole line is kept so that syntax errors in expressions point
+// at the right column
+fn parse_args(cmd: &Command, line: &str, mut pos: usize) -> Result<Args, Error> {
+    let mut args = Args::default();
+    for p in &cmd.params {
+        let mut given = false;
+        loop {
+            let n = if p.kind.rest() { 1 } else { 2 };
+            let arg = match split::split_n_whitespace(&line[pos..], n).next() {
+                Some(arg) => arg,
+                None if p.optional || given => break,
+                None => {
+                    return Err(Error::BadArgument(format!(
+                        "Command `{}` expected {}",
+                        cmd.name, p
+                    )))
+                }
+            };
 
-fn limit_command(_: &[Command], cfg: &mut Config, _: &str, arg: Option<Match>) {
-    if let Some(arg) = arg {
-        let mut it = split::split_n_whitespace(arg.slice, 2);
-        if let Some(fst) = it.next() {
-            if let Some(rest) = it.next() {
-                eprintln!("Unexpected trailing characters `{:?}`.", rest.slice);
-                return;
+            let start = pos + arg.start;
+            let slice = if p.kind.rest() {
+                arg.slice.trim_end()
+            } else {
+                arg.slice
+            };
+            args.0.push((p.name, value(p.kind, slice, &line[..start])?));
+            pos = start + slice.len();
+            given = true;
+            if !p.repeated {
+                break;
             }
+        }
+    }
+
+    let rest = line[pos..].trim();
+    if !rest.is_empty() {
+        return Err(Error::BadArgument(format!(
+            "Unexpected trailing characters `{}`",
+            rest
+        )));
+    }
+    Ok(args)
+}
 
-            match fst.slice.parse::<usize>() {
-                Ok(n) => cfg.limit = n,
-                Err(e) => eprintln!("Expected number but got `{}`.", e),
+// Parse a single argument, prefix being the line before it
+fn value(kind: Kind, s: &str, prefix: &str) -> Result<Value, Error> {
+    let v = match kind {
+        Kind::Number => match s.parse::<usize>() {
+            Ok(n) => Value::Number(n),
+            Err(_) => {
+                return Err(Error::BadArgument(format!(
+                    "Expected number but got `{}`",
+                    s
+                )))
             }
-        } else {
-            println!("{}", cfg.limit)
+        },
+        Kind::Bool => match s {
+            "on" | "true" => Value::Bool(true),
+            "off" | "false" => Value::Bool(false),
+            _ => {
+                return Err(Error::BadArgument(format!(
+                    "Expected on or off but got `{}`",
+                    s
+                )))
+            }
+        },
+        // Whatever parses as a lone variable, so not true or 3
+        Kind::Name => match parse::parse(s) {
+            Some(Ok(Expr::Var(x))) if x == s => Value::Name(x),
+            _ => return Err(Error::BadArgument(format!("Expected name but got `{}`", s))),
+        },
+        Kind::Word => Value::Word(s.to_string()),
+        Kind::Expr => match parse::parse(s) {
+            Some(Ok(e)) => Value::Expr(e),
+            Some(Err(e)) => return Err(e.shift(prefix).into()),
+            // Only a comment
+            None => return Err(Error::BadArgument(String::from("Expected expression"))),
+        },
+        Kind::Path => Value::Path(PathBuf::from(s)),
+        Kind::Text => Value::Text(s.to_string()),
+    };
+    Ok(v)
+}
+
+/// Commands of a session, which library users can extend
+///
+/// A command is found by its name, then by one of its aliases, then by
+/// an abbreviation starting only one command name.
+///
+/// ```
+/// use rewriter::{Buffer, Command, Kind, Param, Session};
+/// use std::cell::Cell;
+/// use std::rc::Rc;
+///
+/// let buf = Buffer::default();
+/// let mut s = Session::with_output(Box::new(buf.clone()));
+/// let total = Rc::new(Cell::new(0));
+/// let t = total.clone();
+/// s.commands_mut().register(
+///     Command::new("count", "Add to the count", move |_, cfg, args| {
+///         t.set(t.get() + args.number("num").unwrap_or(1));
+///         cfg.output().print(&t.get().to_string());
+///         Ok(())
+///     })
+///     .param(Param::new("num", Kind::Number).optional())
+///     .alias("c"),
+/// );
+/// s.eval(":count 2").unwrap();
+/// s.eval(":c").unwrap();
+/// s.eval(":cou").unwrap();
+/// assert_eq!(buf.lines(), ["2", "3", "4"]);
+/// assert!(s.eval(":count x").is_err());
+/// ```
+#[derive(Clone, Default)]
+pub struct CommandRegistry {
+    cmds: Vec<Command>,
+}
+
+impl CommandRegistry {
+    /// Registry without any command
+    pub fn new() -> Self {
+        CommandRegistry::default()
+    }
+
+    /// Registry with the commands of the prompt
+    pub fn builtin() -> Self {
+        let mut cmds = CommandRegistry::new();
+        for cmd in builtins() {
+            cmds.register(cmd);
+        }
+        cmds
+    }
+
+    /// Add a command, replacing the one of the same name if any
+    pub fn register(&mut self, cmd: Command) {
+        match self.cmds.iter_mut().find(|c| c.name == cmd.name) {
+            Some(c) => *c = cmd,
+            None => self.cmds.push(cmd),
         }
-    } else {
-        println!("{}", cfg.limit)
+    }
+
+    /// Remove the command called name
+    pub fn unregister(&mut self, name: &str) -> Option<Command> {
+        let i = self.cmds.iter().position(|c| c.name == name)?;
+        Some(self.cmds.remove(i))
+    }
+
+    /// Commands in registration order
+    pub fn iter(&self) -> impl Iterator<Item = &Command> {
+        self.cmds.iter()
+    }
+
+    /// Command run by :name
+    pub fn resolve(&self, name: &str) -> Result<&Command, Error> {
+        if let Some(cmd) = self.cmds.iter().find(|c| c.name == name) {
+            return Ok(cmd);
+        }
+        if let Some(cmd) = self
+            .cmds
+            .iter()
+            .find(|c| c.aliases.iter().any(|a| a == name))
+        {
+            return Ok(cmd);
+        }
+
+        let prefixed: Vec<&Command> = self
+            .cmds
+            .iter()
+            .filter(|c| c.name.starts_with(name))
+            .collect();
+        match prefixed[..] {
+            [cmd] => Ok(cmd),
+            [] => Err(Error::UnknownCommand {
+                name: name.to_string(),
+                similar: self.similar(name),
+            }),
+            _ => Err(Error::AmbiguousCommand {
+                name: name.to_string(),
+                candidates: prefixed.iter().map(|c| c.name.clone()).collect(),
+            }),
+        }
+    }
+
+    // Names of the commands a typo of name could be meant for, closest
+    // first
+    //
+    // :bnd x
+    // Unrecognized command `bnd`, similar commands are :bind
+    fn similar(&self, name: &str) -> Vec<String> {
+        let mut close: Vec<(usize, &str)> = self
+            .cmds
+            .iter()
+            .map(|c| (distance(name, &c.name), c.name.as_str()))
+            .filter(|&(d, _)| d <= MAX_DISTANCE && d < name.chars().count())
+            .collect();
+        close.sort();
+        close.into_iter().map(|(_, c)| c.to_string()).collect()
+    }
+
+    /// Run the command in line, which still includes the leading :
+    pub fn run(&self, cfg: &mut Config, line: &str) -> Result<(), Error> {
+        let (name, pos) = match split::split_n_whitespace(&line[1..], 2).next() {
+            Some(m) => (m.slice, 1 + m.end),
+            None => ("help", line.len()),
+        };
+        let cmd = self.resolve(name)?;
+        let args = parse_args(cmd, line, pos)?;
+        (cmd.fun)(self, cfg, &args)
     }
 }
 
-pub const ECHO_COMMAND: Command<'static> = Command {
-    name: "echo",
-    usage: ":echo [bool]",
-    desc: "Manipulate whether to echo lines",
-    args: Arg::NoCheck,
-    fun: echo_command,
-};
+// Edits from a typo to the commands suggested instead
+const MAX_DISTANCE: usize = 2;
 
-fn echo_command(_: &[Command], cfg: &mut Config, _: &str, arg: Option<Match>) {
-    if let Some(arg) = arg {
-        let mut it = split::split_n_whitespace(arg.slice, 2);
-        if let Some(fst) = it.next() {
-            if let Some(rest) = it.next() {
-                eprintln!("Unexpected trailing characters `{:?}`.", rest.slice);
-                return;
-            }
+// Levenshtein distance, the number of characters to insert, delete or
+// replace to turn a into b
+fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, x) in a.chars().enumerate() {
+        let mut cur = vec![i + 1];
+        for (j, y) in b.iter().enumerate() {
+            let replace = prev[j] + usize::from(x != *y);
+            cur.push(replace.min(prev[j + 1] + 1).min(cur[j] + 1));
+        }
+        prev = cur;
+    }
+    prev[b.len()]
+}
+
+const HELP_PADDING: usize = 20;
+
+fn builtins() -> Vec<Command> {
+    vec![
+        Command::new("bind", "Define a binding", bind_command)
+            .param(Param::new("name", Kind::Name))
+            .param(Param::new("expr", Kind::Expr))
+            .alias("b")
+            .help(
+                "\
+Bindings are unfolded when they reach the head of the term being
+reduced, and results are folded back into their names. Binding a
+name again replaces its previous definition.
+
+Examples:
+    :bind I \\x.x
+    :bind K \\x y.x
+    :bind S \\x y z.x z (y z)",
+            ),
+        toggle(
+            "compact",
+            "Manipulate whether to print abstractions compactly",
+            |cfg| &mut cfg.compact,
+        )
+        .help(
+            "\
+When on, nested abstractions are printed with a single lambda.
+Off by default.
+
+Examples:
+    :compact on
+    \\x.\\y.x           prints λx y.x",
+        ),
+        Command::new(
+            "debruijn",
+            "Display an expression with de Bruijn indices",
+            debruijn_command,
+        )
+        .param(Param::new("expr", Kind::Expr))
+        .help(
+            "\
+Prints the expression with variables replaced by the number of
+binders between them and their own, then converted back with fresh
+names. Bindings are not unfolded.
+
+Examples:
+    :debruijn \\x y.x      prints λ.λ.1",
+        ),
+        Command::new(
+            "deps",
+            "Display the bindings a binding depends on",
+            deps_command,
+        )
+        .param(Param::new("name", Kind::Name))
+        .help(
+            "\
+Prints each binding reachable from name, depth first, with the
+bindings its definition refers to.
+
+Examples:
+    :prelude
+    :deps fact",
+        ),
+        toggle("echo", "Manipulate whether to echo lines", |cfg| {
+            &mut cfg.echo
+        })
+        .help(
+            "\
+When on, the lines of loaded files are printed before being
+interpreted, and bindings are printed as they are defined. Off by
+default.
+
+Examples:
+    :echo on
+    :file combinator",
+        ),
+        toggle(
+            "eta",
+            "Manipulate whether to perform eta reduction",
+            |cfg| &mut cfg.eta,
+        )
+        .help(
+            "\
+When on, \\x.f x reduces to f if x is not free in f, once no other
+step applies. Off by default.
+
+Examples:
+    :eta on
+    \\x.y x             reduces to y",
+        ),
+        Command::new("file", "Parse and evaluate a file", file_command)
+            .param(Param::new("name", Kind::Path))
+            .alias("f")
+            .help(
+                "\
+Interprets each line of the file as if typed at the prompt,
+reporting errors with the file name and line. Relative names are
+looked up from the including file, then in the directories of
+REWRITER_PATH. A file included twice is only loaded once.
+
+Examples:
+    :file combinator
+    :file recursive",
+            ),
+        toggle(
+            "fold",
+            "Manipulate whether to print results with binding names",
+            |cfg| &mut cfg.fold,
+        )
+        .help(
+            "\
+When on, the final term is also printed with the subterms equal to
+a binding replaced by its name. On by default.
+
+Examples:
+    :bind K \\x y.x
+    \\a b.a             prints λa.λb.a, then = K",
+        ),
+        Command::new("help", "Display the commands or one of them", help_command)
+            .param(Param::new("name", Kind::Word).optional())
+            .alias("h")
+            .alias("?")
+            .help(
+                "\
+Without argument, lists the commands. With the name of a command,
+or one of its aliases, describes it. :help syntax describes the
+syntax of expressions.
+
+Examples:
+    :help limit
+    :help syntax",
+            ),
+        Command::new(
+            "limit",
+            "Manipulate the limit of reduction steps",
+            limit_command,
+        )
+        .param(Param::new("num", Kind::Number).optional())
+        .alias("l")
+        .help(
+            "\
+Reduction stops with an error after num steps, which keeps terms
+without a normal form from running forever. Without argument,
+prints the limit. 100 by default.
+
+Examples:
+    :limit
+    :limit 10000",
+        ),
+        Command::new(
+            "load",
+            "Replace the bindings with those of a file",
+            load_command,
+        )
+        .param(Param::new("name", Kind::Path))
+        .help(
+            "\
+Removes every binding, then interprets the file like :file. Used
+to restore bindings written with :save.
+
+Examples:
+    :save session
+    :load session",
+        ),
+        Command::new(
+            "module",
+            "Load the definitions of a module file",
+            module_command,
+        )
+        .param(Param::new("name", Kind::Path))
+        .help(
+            "\
+Parses the whole file as a sequence of definitions before binding
+them, see :help syntax. Nothing is defined if the file has a syntax
+error.
+
+Examples:
+    :module lib.lc",
+        ),
+        Command::new(
+            "prelude",
+            "Load the standard prelude or check its examples",
+            prelude_command,
+        )
+        .param(Param::new("check", Kind::Word).optional())
+        .help(
+            "\
+Binds the combinators, booleans, numerals, pairs, options, lists
+and recursion combinators of the standard prelude. With check,
+reduces the examples of the prelude and reports those not reaching
+the expected normal form.
+
+Examples:
+    :prelude
+    :prelude check",
+        ),
+        Command::new("reset", "Remove all bindings", reset_command).help(
+            "\
+Files loaded so far are forgotten too, so that loading them again
+defines their bindings again.",
+        ),
+        Command::new("save", "Write the bindings to a file", save_command)
+            .param(Param::new("name", Kind::Path))
+            .help(
+                "\
+Writes a script of :bind commands in definition order, to be read
+back with :load or :file.
+
+Examples:
+    :save session",
+            ),
+        Command::new("show", "Display bindings", show_command)
+            .param(Param::new("name", Kind::Name).optional().repeated())
+            .alias("s")
+            .help(
+                "\
+Without argument, prints every binding in definition order.
+
+Examples:
+    :show
+    :show S K I",
+            ),
+        toggle(
+            "stats",
+            "Manipulate whether to report reduction statistics",
+            |cfg| &mut cfg.stats,
+        )
+        .help(
+            "\
+When on, each reduction is followed by the number of steps of each
+kind, the size of the terms and the time taken. On by default.
 
-            match fst.slice.parse::<bool>() {
-                Ok(b) => cfg.echo = b,
-                Err(e) => eprintln!("Expected boolean but got `{}`.", e),
+Examples:
+    :stats off",
+        ),
+        Command::new(
+            "strategy",
+            "Manipulate the reduction strategy",
+            strategy_command,
+        )
+        .param(Param::new("name", Kind::Word).optional())
+        .help(
+            "\
+Chooses which redex is reduced first: normal, applicative, cbn
+(call by name), cbv (call by value), head or whnf (weak head normal
+form). Without argument, prints the strategy. normal by default.
+
+Examples:
+    :strategy
+    :strategy cbv",
+        ),
+        Command::new(
+            "trace",
+            "Manipulate which reduction steps are printed",
+            trace_command,
+        )
+        .param(Param::new("mode", Kind::Text).optional())
+        .alias("t")
+        .help(
+            "\
+Prints every term of the reduction with all, only the final term
+with final, every Nth term with every N, or nothing with none.
+Without argument, prints the mode. all by default.
+
+Examples:
+    :trace final
+    :trace every 10",
+        ),
+        Command::new("unbind", "Remove bindings", unbind_command)
+            .param(Param::new("name", Kind::Name).repeated())
+            .help(
+                "\
+Examples:
+    :unbind S K",
+            ),
+    ]
+}
+
+// Command printing or setting one of the on|off settings
+fn toggle(name: &str, desc: &str, field: fn(&mut Config) -> &mut bool) -> Command {
+    Command::new(name, desc, move |_, cfg, args| {
+        match args.bool("on|off") {
+            Some(b) => *field(cfg) = b,
+            None => {
+                let b = *field(cfg);
+                out!(cfg, "{}", if b { "on" } else { "off" })
             }
-        } else {
-            println!("{}", cfg.limit)
         }
-    } else {
-        println!("{}", cfg.echo)
+        Ok(())
+    })
+    .param(Param::new("on|off", Kind::Bool).optional())
+}
+
+fn limit_command(_: &CommandRegistry, cfg: &mut Config, args: &Args) -> Result<(), Error> {
+    match args.number("num") {
+        Some(n) => cfg.limit = n,
+        None => out!(cfg, "{}", cfg.limit),
     }
+    Ok(())
 }
 
-pub const BIND_COMMAND: Command<'static> = Command {
-    name: "bind",
-    usage: ":bind name expr",
-    desc: "Define a binding",
-    args: Arg::CheckSome,
-    fun: bind_command,
-};
+fn strategy_command(_: &CommandRegistry, cfg: &mut Config, args: &Args) -> Result<(), Error> {
+    match args.str("name") {
+        Some(name) => match name.parse::<Strategy>() {
+            Ok(st) => cfg.strategy = st,
+            Err(e) => {
+                let all: Vec<String> = Strategy::ALL.iter().map(|st| st.to_string()).collect();
+                return Err(Error::BadArgument(format!(
+                    "{}, available strategies are {}",
+                    e,
+                    all.join(", ")
+                )));
+            }
+        },
+        None => out!(cfg, "{}", cfg.strategy),
+    }
+    Ok(())
+}
 
-fn bind_command(_: &[Command], cfg: &mut Config, _: &str, arg: Option<Match>) {
-    // Should be checked in command()
-    debug_assert!(arg.is_some());
-    let arg = arg.unwrap();
-
-    let mut it = split::split_n_whitespace(arg.slice, 2);
-    if let Some(x) = it.next() {
-        if let Some(e) = it.next() {
-            debug_assert!(it.next().is_none());
-            match parse::parse(e.slice) {
-                Some(Ok(e)) => {
-                    if cfg.echo {
-                        println!("{} = {}", x.slice, &e)
-                    }
+fn trace_command(_: &CommandRegistry, cfg: &mut Config, args: &Args) -> Result<(), Error> {
+    match args.str("mode") {
+        Some(mode) => cfg.trace = mode.parse::<Trace>().map_err(Error::BadArgument)?,
+        None => out!(cfg, "{}", cfg.trace),
+    }
+    Ok(())
+}
 
-                    cfg.bind.insert(x.slice.to_string(), e.clone());
-                    cfg.subst = cfg.subst.extend(x.slice.to_string(), e.clone());
-                }
-                Some(Err(e)) => eprintln!("{}", e),
-                None => eprintln!("Expected expression after binding."),
-            };
-        } else {
-            eprintln!("Expected expression after binding.")
+fn bind_command(_: &CommandRegistry, cfg: &mut Config, args: &Args) -> Result<(), Error> {
+    // Both checked by parse_args()
+    let x = args.str("name").unwrap();
+    let e = args.expr("expr").unwrap();
+    define(cfg, x, e.clone());
+    Ok(())
+}
+
+fn define(cfg: &mut Config, x: &str, e: Expr) {
+    if cfg.echo {
+        out!(cfg, "{} = {}", x, cfg.show(&e))
+    }
+
+    // A redefinition moves the binding last, as if it was new
+    cfg.bind.retain(|(y, _)| y != x);
+    cfg.bind.push((x.to_string(), e.clone()));
+    cfg.subst = cfg.subst.insert(x.to_string(), e);
+}
+
+// Build the substitution again from the bindings, in definition order
+fn rebind(cfg: &mut Config) {
+    cfg.subst = cfg
+        .bind
+        .iter()
+        .fold(Subst::new(), |s, (x, e)| s.insert(x.clone(), e.clone()));
+}
+
+fn unbind_command(_: &CommandRegistry, cfg: &mut Config, args: &Args) -> Result<(), Error> {
+    let mut missing = vec![];
+    for x in args.strs("name") {
+        let len = cfg.bind.len();
+        cfg.bind.retain(|(y, _)| y != x);
+        if cfg.bind.len() == len {
+            missing.push(x);
         }
-    } else {
-        eprintln!("Expected binding name and expression.")
+    }
+    rebind(cfg);
+
+    match missing.first() {
+        Some(x) => Err(not_found(x)),
+        None => Ok(()),
     }
 }
 
-pub const FILE_COMMAND: Command<'static> = Command {
-    name: "file",
-    usage: ":file name",
-    desc: "Parse and evaluate a file",
-    args: Arg::CheckSome,
-    fun: file_command,
-};
+fn reset_command(_: &CommandRegistry, cfg: &mut Config, _: &Args) -> Result<(), Error> {
+    reset(cfg);
+    Ok(())
+}
 
-fn file_command(cmds: &[Command], cfg: &mut Config, _: &str, arg: Option<Match>) {
-    // Should be checked in command()
-    debug_assert!(arg.is_some());
-    let arg = arg.unwrap();
+// Forget the bindings and the files they came from, so that loading
+// the files again defines them again
+fn reset(cfg: &mut Config) {
+    cfg.bind.clear();
+    cfg.loaded.clear();
+    rebind(cfg);
+}
+
+fn save_command(_: &CommandRegistry, cfg: &mut Config, args: &Args) -> Result<(), Error> {
+    // A script of :bind commands, in definition order so that
+    // redefinitions are replayed the same way
+    let mut script = String::from("-- Saved bindings\n");
+    for (x, e) in &cfg.bind {
+        script.push_str(&format!(":bind {} {}\n", x, e));
+    }
+
+    let path = args.path("name").unwrap();
+    fs::write(path, script).map_err(|err| Error::Io {
+        name: path.display().to_string(),
+        err,
+    })
+}
+
+fn load_command(cmds: &CommandRegistry, cfg: &mut Config, args: &Args) -> Result<(), Error> {
+    // The bindings are kept when the file cannot be read
+    let name = args.path("name").unwrap();
+    resolve(cfg, name)
+        .and_then(File::open)
+        .map_err(|err| Error::Io {
+            name: name.display().to_string(),
+            err,
+        })?;
+
+    reset(cfg);
+    load(cmds, cfg, name)
+}
+
+fn debruijn_command(_: &CommandRegistry, cfg: &mut Config, args: &Args) -> Result<(), Error> {
+    let t = Term::from(args.expr("expr").unwrap());
+    out!(cfg, "{}", t);
+    out!(cfg, "{}", cfg.show(&Expr::from(&t)));
+    Ok(())
+}
+
+fn file_command(cmds: &CommandRegistry, cfg: &mut Config, args: &Args) -> Result<(), Error> {
+    load(cmds, cfg, args.path("name").unwrap())
+}
+
+// Variable holding the directories searched for files, separated
+// like PATH
+const SEARCH_PATH: &str = "REWRITER_PATH";
+
+// Find the file at path
+//
+// A relative path is looked up in the directory of the file being
+// loaded (or the current directory at the top level), then in each
+// directory of REWRITER_PATH
+fn resolve(cfg: &Config, path: &Path) -> io::Result<PathBuf> {
+    if path.is_absolute() {
+        return path.canonicalize();
+    }
+
+    let base = match cfg.files.last().and_then(|f| f.parent()) {
+        Some(dir) => dir.join(path),
+        None => path.to_path_buf(),
+    };
+    let err = match base.canonicalize() {
+        Ok(f) => return Ok(f),
+        Err(e) => e,
+    };
+
+    let dirs = env::var_os(SEARCH_PATH).unwrap_or_default();
+    env::split_paths(&dirs)
+        .find_map(|dir| dir.join(path).canonicalize().ok())
+        .ok_or(err)
+}
+
+// Push the file called name on the include stack, false if it must not
+// be loaded
+//
+// A file already on the stack would include itself, and a file already
+// loaded is skipped when included by another file, so that the files
+// they share are only loaded once
+fn enter(cfg: &mut Config, name: &Path) -> Result<bool, Error> {
+    let path = resolve(cfg, name).map_err(|err| Error::Io {
+        name: name.display().to_string(),
+        err,
+    })?;
+
+    if let Some(i) = cfg.files.iter().position(|f| *f == path) {
+        let mut files = cfg.files[i..].to_vec();
+        files.push(path);
+        return Err(Error::ImportCycle(files));
+    }
+
+    if !cfg.files.is_empty() && cfg.loaded.contains(&path) {
+        return Ok(false);
+    }
+
+    cfg.loaded.insert(path.clone());
+    cfg.files.push(path);
+    Ok(true)
+}
 
+// Interpret each line of the file
+//
+// Errors in the file are reported as they happen, only those
+// preventing it from being read are returned
+pub fn load(cmds: &CommandRegistry, cfg: &mut Config, name: &Path) -> Result<(), Error> {
     fn read(path: &PathBuf) -> io::Result<io::Lines<io::BufReader<File>>> {
         let file = File::open(path)?;
         Ok(io::BufReader::new(file).lines())
     }
 
-    let name = arg.slice.trim_end();
-    let path = {
-        match Path::new(name).canonicalize() {
-            Ok(f) => f,
-            Err(e) => {
-                eprintln!("File `{}` failed to load {}", name, e);
-                return;
+    if !enter(cfg, name)? {
+        return Ok(());
+    }
+
+    let prev_line = cfg.line;
+    let res = match read(cfg.files.last().unwrap()) {
+        Ok(lines) => {
+            let mut buf = String::new();
+            let mut start = 0;
+            let mut res = Ok(());
+            for (i, line) in lines.enumerate() {
+                let line = match line {
+                    Ok(line) => line,
+                    // A line that is not UTF-8 is reported and skipped
+                    Err(err) if err.kind() == io::ErrorKind::InvalidData => {
+                        cfg.line = i + 1;
+                        let e = Error::Io {
+                            name: name.display().to_string(),
+                            err,
+                        };
+                        cfg.report("", &e);
+                        continue;
+                    }
+                    Err(err) => {
+                        res = Err(Error::Io {
+                            name: name.display().to_string(),
+                            err,
+                        });
+                        break;
+                    }
+                };
+                if buf.is_empty() {
+                    start = i + 1;
+                    buf = line;
+                } else {
+                    parse::join(&mut buf, &line);
+                }
+                if parse::incomplete(&buf) {
+                    continue;
+                }
+
+                cfg.line = start;
+                if cfg.echo {
+                    for (j, line) in buf.lines().enumerate() {
+                        out!(cfg, "{} {}", if j == 0 { ">>" } else { ".." }, line)
+                    }
+                }
+                let _ = crate::interpret(cmds, cfg, &buf);
+                buf.clear();
+                if interrupt::pending() {
+                    break;
+                }
             }
+
+            // Unterminated input at the end of the file
+            if !buf.is_empty() {
+                cfg.line = start;
+                let _ = crate::interpret(cmds, cfg, &buf);
+            }
+            res
         }
+        Err(err) => Err(Error::Io {
+            name: name.display().to_string(),
+            err,
+        }),
     };
+    cfg.files.pop();
+    cfg.line = prev_line;
+    res
+}
 
-    if let Some(f) = &cfg.file {
-        if *f == path {
-            eprintln!("File `{}` loads itself.", path.display());
-            return;
-        }
+fn module_command(_: &CommandRegistry, cfg: &mut Config, args: &Args) -> Result<(), Error> {
+    load_module(cfg, args.path("name").unwrap())
+}
+
+// Parse the whole file as a program, then define each of its declarations
+//
+// Syntax errors are reported against the file rather than returned
+pub fn load_module(cfg: &mut Config, name: &Path) -> Result<(), Error> {
+    if !enter(cfg, name)? {
+        return Ok(());
     }
 
-    let prev = cfg.file.replace(path);
-    match read(cfg.file.as_ref().unwrap()) {
-        Ok(lines) => {
-            for line in lines {
-                if let Ok(line) = line {
-                    if cfg.echo {
-                        println!(">> {}", line)
+    let prev_line = cfg.line;
+    cfg.line = 1;
+    let res = match fs::read_to_string(cfg.files.last().unwrap()) {
+        Ok(src) => {
+            match parse::parse_program(&src) {
+                Ok(decls) => {
+                    for d in decls {
+                        define(cfg, &d.name, d.expr)
                     }
-                    if crate::interpret(cmds, cfg, &line) {
-                        break;
+                }
+                Err(e) => cfg.report(&src, &e.into()),
+            }
+            Ok(())
+        }
+        Err(err) => Err(Error::Io {
+            name: name.display().to_string(),
+            err,
+        }),
+    };
+    cfg.files.pop();
+    cfg.line = prev_line;
+    res
+}
+
+fn prelude_command(_: &CommandRegistry, cfg: &mut Config, args: &Args) -> Result<(), Error> {
+    // The prelude is checked with :prelude check, an error is a bug
+    let decls = parse::parse_program(prelude::SOURCE)?;
+
+    match args.str("check") {
+        None => {
+            for d in decls {
+                define(cfg, &d.name, d.expr)
+            }
+        }
+        Some("check") => {
+            // Examples only see the prelude, not the current bindings
+            let mut s = Subst::new();
+            for d in decls {
+                s = s.insert(d.name, d.expr);
+            }
+
+            let normalize = |src: &str| match parse::parse(src) {
+                Some(Ok(e)) => prelude::normalize(e, &s),
+                _ => None,
+            };
+            let mut failed = 0;
+            let mut total = 0;
+            for ex in prelude::examples() {
+                total += 1;
+                match (normalize(ex.expr), normalize(ex.normal)) {
+                    (Some(e), Some(nf)) if e.alpha_eq(&nf) => {}
+                    (e, _) => {
+                        failed += 1;
+                        let got = e.map_or(String::from("no normal form"), |e| cfg.show(&e));
+                        err!(
+                            cfg,
+                            "{}:{}: `{}` expected `{}` but got `{}`",
+                            prelude::ORIGIN,
+                            ex.line,
+                            ex.expr,
+                            ex.normal,
+                            got
+                        )
                     }
                 }
             }
+            out!(cfg, "-- {} examples, {} failed", total, failed);
+            if failed > 0 {
+                cfg.failed = true;
+            }
+        }
+        Some(s) => {
+            return Err(Error::BadArgument(format!(
+                "Expected check but got `{}`",
+                s
+            )))
         }
-        Err(e) => eprintln!("{}", e),
     }
-    cfg.file = prev;
+    Ok(())
 }
 
-pub const HELP_COMMAND: Command<'static> = Command {
-    name: "help",
-    usage: ":help",
-    desc: "Display this menu",
-    args: Arg::CheckNone,
-    fun: help_command,
-};
+// Without argument the list of commands, otherwise the description of
+// a command or of the syntax
+fn help_command(cmds: &CommandRegistry, cfg: &mut Config, args: &Args) -> Result<(), Error> {
+    let name = match args.str("name") {
+        Some("syntax") => {
+            for line in SYNTAX.lines() {
+                out!(cfg, "{}", line)
+            }
+            return Ok(());
+        }
+        Some(name) => name,
+        None => {
+            out!(cfg, "Available commands:");
+            for cmd in cmds.iter() {
+                let usage = cmd.usage();
+                out!(
+                    cfg,
+                    "    {2}{0:1$}{3}",
+                    " ",
+                    HELP_PADDING.saturating_sub(usage.len()),
+                    usage,
+                    cmd.desc
+                )
+            }
+            out!(
+                cfg,
+                "Type :help name for details, :help syntax for expressions"
+            );
+            return Ok(());
+        }
+    };
 
-fn help_command(cmds: &[Command], _: &mut Config, _: &str, _: Option<Match>) {
-    println!("Available commands:");
-    for cmd in cmds {
-        println!(
-            "    {2}{0:1$}{3}",
-            " ",
-            HELP_PADDING - cmd.usage.len(),
-            cmd.usage,
-            cmd.desc
-        )
+    let cmd = cmds.resolve(name)?;
+    out!(cfg, "{}", cmd.usage());
+    out!(cfg, "    {}", cmd.desc);
+    if !cmd.aliases.is_empty() {
+        out!(cfg, "    Aliases: :{}", cmd.aliases.join(", :"))
+    }
+    if !cmd.help.is_empty() {
+        out!(cfg, "");
+        for line in cmd.help.lines() {
+            out!(cfg, "{}", line)
+        }
     }
+    Ok(())
 }
 
-pub const SHOW_COMMAND: Command<'static> = Command {
-    name: "show",
-    usage: ":show",
-    desc: "Display bindings",
-    args: Arg::CheckNone,
-    fun: show_command,
-};
+// Grammar of parse.rs, printed by :help syntax
+const SYNTAX: &str = "\
+Expressions:
+    x                   Variable, letters and _ then primes: f, x_y'
+    \\x.e  λx.e          Abstraction, the body extending as far as possible
+    \\x y z.e            Abstraction of several parameters, \\x.\\y.\\z.e
+    e1 e2 e3            Application, left associative: (e1 e2) e3
+    (e)                 Grouping
+    let f x = e1 in e2  Local definition of f, in scope in e2 only
+    0 1 2 ...           Church numerals up to 1000, \\f x.f (f x) for 2
+    true false          Church booleans, \\x y.x and \\x y.y
+    -- comment          Ignored up to the end of the line
 
-fn show_command(_: &[Command], cfg: &mut Config, _: &str, _: Option<Match>) {
-    println!("Bindings:");
-    for (k, v) in cfg.bind.iter() {
-        println!("{} = {}", k, v)
+Lines starting with : are commands, see :help. A line ending with \\
+or with unclosed parentheses continues on the next line, the \\
+being dropped unless it follows ( . = or a name and so starts an
+abstraction:
+    :bind theta (\\x y.y (x x y)) \\
+        (\\x y.y (x x y))
+
+Module files, loaded with :module, are sequences of definitions
+each ended by a semicolon, the parameters becoming abstractions:
+    K = \\x y.x;
+    S x y z = x z (y z);";
+
+fn show_command(_: &CommandRegistry, cfg: &mut Config, args: &Args) -> Result<(), Error> {
+    let names = args.strs("name");
+    if names.is_empty() {
+        out!(cfg, "Bindings:");
+        for (k, v) in &cfg.bind {
+            out!(cfg, "{} = {}", k, cfg.show(v))
+        }
+    }
+    for x in names {
+        let e = lookup(&cfg.bind, x).ok_or_else(|| not_found(x))?;
+        out!(cfg, "{} = {}", x, cfg.show(e))
+    }
+    Ok(())
+}
+
+fn lookup<'a>(bind: &'a [(String, Expr)], x: &str) -> Option<&'a Expr> {
+    bind.iter().find(|(y, _)| y == x).map(|(_, e)| e)
+}
+
+fn not_found(x: &str) -> Error {
+    Error::BadArgument(format!("Binding `{}` not found", x))
+}
+
+// Each binding reachable from name with the bindings it refers to,
+// depth first
+//
+// :deps fact
+// fact -> fix, if, iszero, mul, pred
+// fix
+// if
+// ...
+fn deps_command(_: &CommandRegistry, cfg: &mut Config, args: &Args) -> Result<(), Error> {
+    let name = args.str("name").unwrap();
+    if lookup(&cfg.bind, name).is_none() {
+        return Err(not_found(name));
+    }
+
+    let mut seen = vec![name];
+    let mut stack = vec![name];
+    while let Some(x) = stack.pop() {
+        let deps: Vec<&str> = lookup(&cfg.bind, x)
+            .map(|e| e.freevars())
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|y| lookup(&cfg.bind, y).is_some())
+            .collect();
+
+        if deps.is_empty() {
+            out!(cfg, "{}", x)
+        } else {
+            out!(cfg, "{} -> {}", x, deps.join(", "))
+        }
+
+        for y in deps.into_iter().rev() {
+            if !seen.contains(&y) {
+                seen.push(y);
+                stack.push(y);
+            }
+        }
     }
+    Ok(())
 }
@@ -0,0 +1,138 @@
+use crate::Expr;
+use std::fmt;
+
+// Nameless representation of expressions
+//
+// Bound variables are replaced by the number of binders between
+// their occurrence and their binder, free variables keep their name
+//
+// \x.\y.x y z
+// λ.λ.1 0 z
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Term {
+    App(Box<Term>, Box<Term>),
+    Lam(Box<Term>),
+    Let(Box<Term>, Box<Term>),
+    Bound(usize),
+    Free(String),
+}
+
+impl Term {
+    fn from_expr<'a>(e: &'a Expr, ctx: &mut Vec<&'a str>) -> Term {
+        match e {
+            Expr::App(e1, e2) => Term::App(
+                Box::new(Term::from_expr(e1, ctx)),
+                Box::new(Term::from_expr(e2, ctx)),
+            ),
+            Expr::Lam(x, e) => {
+                ctx.push(x);
+                let t = Term::from_expr(e, ctx);
+                ctx.pop();
+                Term::Lam(Box::new(t))
+            }
+            Expr::Let(x, e1, e2) => {
+                let t1 = Term::from_expr(e1, ctx);
+                ctx.push(x);
+                let t2 = Term::from_expr(e2, ctx);
+                ctx.pop();
+                Term::Let(Box::new(t1), Box::new(t2))
+            }
+            Expr::Var(x) => match ctx.iter().rev().position(|y| y == x) {
+                Some(i) => Term::Bound(i),
+                None => Term::Free(x.clone()),
+            },
+        }
+    }
+
+    fn free<'a>(&'a self, acc: &mut Vec<&'a str>) {
+        match self {
+            Term::App(t1, t2) | Term::Let(t1, t2) => {
+                t1.free(acc);
+                t2.free(acc);
+            }
+            Term::Lam(t) => t.free(acc),
+            Term::Bound(_) => {}
+            Term::Free(x) => acc.push(x),
+        }
+    }
+
+    fn to_expr(&self, names: &[String], depth: usize) -> Expr {
+        match self {
+            Term::App(t1, t2) => Expr::app(t1.to_expr(names, depth), t2.to_expr(names, depth)),
+            Term::Lam(t) => Expr::lam(&names[depth], t.to_expr(names, depth + 1)),
+            Term::Let(t1, t2) => Expr::let_(
+                &names[depth],
+                t1.to_expr(names, depth),
+                t2.to_expr(names, depth + 1),
+            ),
+            Term::Bound(i) => Expr::var(&names[depth - i - 1]),
+            Term::Free(x) => Expr::var(x),
+        }
+    }
+
+    fn depth(&self) -> usize {
+        match self {
+            Term::App(t1, t2) => t1.depth().max(t2.depth()),
+            Term::Lam(t) => t.depth() + 1,
+            Term::Let(t1, t2) => t1.depth().max(t2.depth() + 1),
+            Term::Bound(_) | Term::Free(_) => 0,
+        }
+    }
+
+    fn is_atom(&self) -> bool {
+        matches!(self, Term::Bound(_) | Term::Free(_))
+    }
+}
+
+impl From<&Expr> for Term {
+    fn from(e: &Expr) -> Self {
+        Term::from_expr(e, &mut vec![])
+    }
+}
+
+// Binders are named after their depth, a, b, ..., z, a', b', ...,
+// skipping the names of free variables so that none is captured
+impl From<&Term> for Expr {
+    fn from(t: &Term) -> Self {
+        let mut free = vec![];
+        t.free(&mut free);
+
+        let mut names = vec![];
+        let mut i = 0;
+        while names.len() < t.depth() {
+            let mut x = String::from((b'a' + (i % 26) as u8) as char);
+            x.extend(std::iter::repeat_n('\'', i / 26));
+            if !free.contains(&x.as_str()) {
+                names.push(x);
+            }
+            i += 1;
+        }
+
+        t.to_expr(&names, 0)
+    }
+}
+
+impl fmt::Display for Term {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Term::App(t1, t2) => {
+                let v1 = t1.is_atom();
+                let v2 = t2.is_atom();
+                write!(
+                    f,
+                    "{}{}{} {}{}{}",
+                    if !v1 { "(" } else { "" },
+                    t1,
+                    if !v1 { ")" } else { "" },
+                    if !v2 { "(" } else { "" },
+                    t2,
+                    if !v2 { ")" } else { "" }
+                )
+            }
+            Term::Lam(t) => write!(f, "λ.{}", t),
+            Term::Let(t1, t2) => write!(f, "let {} in {}", t1, t2),
+            Term::Bound(i) => write!(f, "{}", i),
+            Term::Free(x) => write!(f, "{}", x),
+        }
+    }
+}
@@ -0,0 +1,425 @@
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+
+const HISTORY_FILE: &str = ".rewriter_history";
+const HISTORY_SIZE: usize = 1000;
+
+// Line editor for the prompt
+//
+// When standard input is a terminal it is put in raw mode while a line
+// is edited, otherwise lines are read as they are
+pub struct Editor {
+    history: Vec<String>,
+    path: Option<PathBuf>,
+    raw: bool,
+}
+
+// Completions of word, given the part of the line that precedes it
+pub type Complete<'a> = dyn Fn(&str, &str) -> Vec<String> + 'a;
+
+impl Editor {
+    pub fn new() -> Self {
+        let path = env::var_os("HOME").map(|home| PathBuf::from(home).join(HISTORY_FILE));
+        let mut history: Vec<String> = path
+            .as_ref()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .map(|s| s.lines().map(String::from).collect())
+            .unwrap_or_default();
+
+        // Only the most recent lines are kept, in the file too
+        let trim = history.len() > HISTORY_SIZE;
+        if trim {
+            history.drain(..history.len() - HISTORY_SIZE);
+        }
+
+        let editor = Editor {
+            history,
+            path,
+            raw: term::is_tty(),
+        };
+        if trim {
+            editor.save();
+        }
+        editor
+    }
+
+    // Read a line, terminated by a newline unless the input is over
+    pub fn read_line(&mut self, prompt: &str, complete: &Complete) -> io::Result<String> {
+        let mut line = String::new();
+        if !self.raw {
+            print!("{}", prompt);
+            io::stdout().flush()?;
+            io::stdin().read_line(&mut line)?;
+            return Ok(line);
+        }
+
+        let mode = term::Raw::enable()?;
+        let res = self.edit(prompt, complete);
+        drop(mode);
+
+        if let Ok(Some(line)) = &res {
+            println!();
+            self.remember(line);
+        }
+        res.map(|line| line.map(|line| line + "\n").unwrap_or_default())
+    }
+
+    fn remember(&mut self, line: &str) {
+        if line.trim().is_empty() || self.history.last().map(String::as_str) == Some(line) {
+            return;
+        }
+
+        self.history.push(line.to_string());
+        if self.history.len() > HISTORY_SIZE {
+            // The oldest line goes from the file as well
+            self.history.remove(0);
+            self.save();
+        } else if let Some(path) = &self.path {
+            // History is a convenience, failing to save it is not an error
+            if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(path) {
+                let _ = writeln!(file, "{}", line);
+            }
+        }
+    }
+
+    // Write the whole history, replacing the file
+    fn save(&self) {
+        if let Some(path) = &self.path {
+            let mut s = self.history.join("\n");
+            s.push('\n');
+            let _ = fs::write(path, s);
+        }
+    }
+
+    // Edit a line in raw mode, None on Ctrl+D with an empty line
+    fn edit(&mut self, prompt: &str, complete: &Complete) -> io::Result<Option<String>> {
+        let mut buf: Vec<char> = vec![];
+        let mut pos = 0;
+        // Position in the history and the line being edited before it
+        let mut hist = self.history.len();
+        let mut saved: Vec<char> = vec![];
+
+        refresh(prompt, &buf, pos)?;
+        loop {
+            match key()? {
+                Key::Char(c) => {
+                    buf.insert(pos, c);
+                    pos += 1;
+                }
+                Key::Enter => return Ok(Some(buf.into_iter().collect())),
+                Key::Backspace if pos > 0 => {
+                    pos -= 1;
+                    buf.remove(pos);
+                }
+                Key::Delete if pos < buf.len() => {
+                    buf.remove(pos);
+                }
+                Key::Left if pos > 0 => pos -= 1,
+                Key::Right if pos < buf.len() => pos += 1,
+                Key::Home => pos = 0,
+                Key::End => pos = buf.len(),
+                Key::KillEnd => buf.truncate(pos),
+                Key::KillStart => {
+                    buf.drain(..pos);
+                    pos = 0;
+                }
+                Key::KillWord => {
+                    let mut start = pos;
+                    while start > 0 && buf[start - 1].is_whitespace() {
+                        start -= 1;
+                    }
+                    while start > 0 && !buf[start - 1].is_whitespace() {
+                        start -= 1;
+                    }
+                    buf.drain(start..pos);
+                    pos = start;
+                }
+                Key::Up if hist > 0 => {
+                    if hist == self.history.len() {
+                        saved = buf.clone();
+                    }
+                    hist -= 1;
+                    buf = self.history[hist].chars().collect();
+                    pos = buf.len();
+                }
+                Key::Down if hist < self.history.len() => {
+                    hist += 1;
+                    buf = match self.history.get(hist) {
+                        Some(line) => line.chars().collect(),
+                        None => saved.clone(),
+                    };
+                    pos = buf.len();
+                }
+                Key::Tab => {
+                    let start = word_start(&buf[..pos]);
+                    let before: String = buf[..start].iter().collect();
+                    let word: String = buf[start..pos].iter().collect();
+                    let candidates = complete(&before, &word);
+
+                    let common = common_prefix(&candidates);
+                    if candidates.len() == 1 {
+                        let rest: Vec<char> =
+                            candidates[0][word.len()..].chars().chain([' ']).collect();
+                        buf.splice(pos..pos, rest.iter().cloned());
+                        pos += rest.len();
+                    } else if common.len() > word.len() {
+                        let rest: Vec<char> = common[word.len()..].chars().collect();
+                        buf.splice(pos..pos, rest.iter().cloned());
+                        pos += rest.len();
+                    } else if !candidates.is_empty() {
+                        print!("\r\n{}\r\n", candidates.join("  "));
+                    }
+                }
+                Key::Clear => print!("\x1b[H\x1b[2J"),
+                Key::Cancel => {
+                    print!("^C\r\n");
+                    buf.clear();
+                    pos = 0;
+                    hist = self.history.len();
+                }
+                Key::Eof if buf.is_empty() => return Ok(None),
+                Key::Eof if pos < buf.len() => {
+                    buf.remove(pos);
+                }
+                _ => {}
+            }
+            refresh(prompt, &buf, pos)?;
+        }
+    }
+}
+
+enum Key {
+    Char(char),
+    Enter,
+    Tab,
+    Backspace,
+    Delete,
+    Left,
+    Right,
+    Up,
+    Down,
+    Home,
+    End,
+    KillEnd,
+    KillStart,
+    KillWord,
+    Clear,
+    Cancel,
+    Eof,
+    Unknown,
+}
+
+fn byte() -> io::Result<u8> {
+    let mut b = [0u8];
+    if io::stdin().read(&mut b)? == 0 {
+        // Treat a closed input as Ctrl+D
+        return Ok(4);
+    }
+    Ok(b[0])
+}
+
+fn key() -> io::Result<Key> {
+    Ok(match byte()? {
+        b'\r' | b'\n' => Key::Enter,
+        b'\t' => Key::Tab,
+        127 | 8 => Key::Backspace,
+        1 => Key::Home,
+        2 => Key::Left,
+        3 => Key::Cancel,
+        4 => Key::Eof,
+        5 => Key::End,
+        6 => Key::Right,
+        11 => Key::KillEnd,
+        12 => Key::Clear,
+        14 => Key::Down,
+        16 => Key::Up,
+        21 => Key::KillStart,
+        23 => Key::KillWord,
+        // Escape sequences
+        //
+        // ESC [ A   ESC [ 3 ~   ESC O H
+        27 => match byte()? {
+            b'[' | b'O' => match byte()? {
+                b'A' => Key::Up,
+                b'B' => Key::Down,
+                b'C' => Key::Right,
+                b'D' => Key::Left,
+                b'H' => Key::Home,
+                b'F' => Key::End,
+                c @ b'0'..=b'9' => {
+                    let mut c = c;
+                    let mut n = 0;
+                    while c.is_ascii_digit() {
+                        n = n * 10 + (c - b'0') as usize;
+                        c = byte()?;
+                    }
+                    match (n, c) {
+                        (1 | 7, b'~') => Key::Home,
+                        (3, b'~') => Key::Delete,
+                        (4 | 8, b'~') => Key::End,
+                        _ => Key::Unknown,
+                    }
+                }
+                _ => Key::Unknown,
+            },
+            _ => Key::Unknown,
+        },
+        b if b < 32 => Key::Unknown,
+        b => {
+            // Decode the rest of an UTF-8 sequence
+            let len = match b {
+                0xc0..=0xdf => 2,
+                0xe0..=0xef => 3,
+                0xf0..=0xf7 => 4,
+                _ => 1,
+            };
+            let mut bytes = vec![b];
+            for _ in 1..len {
+                bytes.push(byte()?);
+            }
+            match std::str::from_utf8(&bytes)
+                .ok()
+                .and_then(|s| s.chars().next())
+            {
+                Some(c) => Key::Char(c),
+                None => Key::Unknown,
+            }
+        }
+    })
+}
+
+fn refresh(prompt: &str, buf: &[char], pos: usize) -> io::Result<()> {
+    let line: String = buf.iter().collect();
+    let col = prompt.chars().count() + pos;
+    print!("\r{}{}\x1b[K\r", prompt, line);
+    if col > 0 {
+        print!("\x1b[{}C", col);
+    }
+    io::stdout().flush()
+}
+
+// Start of the word ending at the cursor
+fn word_start(buf: &[char]) -> usize {
+    buf.iter()
+        .rposition(|&c| c.is_whitespace() || "():.=\\λ".contains(c))
+        .map_or(0, |i| i + 1)
+}
+
+fn common_prefix(words: &[String]) -> &str {
+    let mut prefix = match words.first() {
+        Some(w) => w.as_str(),
+        None => return "",
+    };
+    for w in &words[1..] {
+        while !w.starts_with(prefix) {
+            let mut it = prefix.chars();
+            it.next_back();
+            prefix = it.as_str();
+        }
+    }
+    prefix
+}
+
+// The layout of termios and the flags below are those of glibc on
+// these architectures, others read lines without editing
+#[cfg(all(
+    target_os = "linux",
+    target_env = "gnu",
+    any(target_arch = "x86", target_arch = "x86_64", target_arch = "aarch64")
+))]
+mod term {
+    use std::io;
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    struct Termios {
+        c_iflag: u32,
+        c_oflag: u32,
+        c_cflag: u32,
+        c_lflag: u32,
+        c_line: u8,
+        c_cc: [u8; 32],
+        c_ispeed: u32,
+        c_ospeed: u32,
+    }
+
+    extern "C" {
+        fn isatty(fd: i32) -> i32;
+        fn tcgetattr(fd: i32, t: *mut Termios) -> i32;
+        fn tcsetattr(fd: i32, act: i32, t: *const Termios) -> i32;
+    }
+
+    const ICRNL: u32 = 0o400;
+    const IXON: u32 = 0o2000;
+    const ISIG: u32 = 0o1;
+    const ICANON: u32 = 0o2;
+    const ECHO: u32 = 0o10;
+    const IEXTEN: u32 = 0o100000;
+    const VTIME: usize = 5;
+    const VMIN: usize = 6;
+    const TCSAFLUSH: i32 = 2;
+
+    pub fn is_tty() -> bool {
+        unsafe { isatty(0) == 1 && isatty(1) == 1 }
+    }
+
+    // Raw mode of standard input, restored when dropped
+    pub struct Raw(Termios);
+
+    impl Raw {
+        pub fn enable() -> io::Result<Raw> {
+            let mut t = Termios {
+                c_iflag: 0,
+                c_oflag: 0,
+                c_cflag: 0,
+                c_lflag: 0,
+                c_line: 0,
+                c_cc: [0; 32],
+                c_ispeed: 0,
+                c_ospeed: 0,
+            };
+            if unsafe { tcgetattr(0, &mut t) } != 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            let orig = t;
+            t.c_iflag &= !(ICRNL | IXON);
+            t.c_lflag &= !(ECHO | ICANON | ISIG | IEXTEN);
+            t.c_cc[VMIN] = 1;
+            t.c_cc[VTIME] = 0;
+            if unsafe { tcsetattr(0, TCSAFLUSH, &t) } != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(Raw(orig))
+        }
+    }
+
+    impl Drop for Raw {
+        fn drop(&mut self) {
+            unsafe { tcsetattr(0, TCSAFLUSH, &self.0) };
+        }
+    }
+}
+
+#[cfg(not(all(
+    target_os = "linux",
+    target_env = "gnu",
+    any(target_arch = "x86", target_arch = "x86_64", target_arch = "aarch64")
+)))]
+mod term {
+    use std::io;
+
+    pub fn is_tty() -> bool {
+        false
+    }
+
+    pub struct Raw;
+
+    impl Raw {
+        pub fn enable() -> io::Result<Raw> {
+            Err(io::Error::new(io::ErrorKind::Unsupported, "raw mode"))
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use crate::parse::ParseError;
+use crate::Outcome;
+use std::error;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+/// Everything that can go wrong when interpreting a line
+///
+/// ```
+/// use rewriter::{Error, Session, Status};
+///
+/// let mut s = Session::new();
+/// assert!(matches!(s.eval("\\x x"), Err(Error::Parse(_))));
+/// assert!(matches!(s.eval(":limit x"), Err(Error::BadArgument(_))));
+/// assert!(matches!(s.eval(":nope"), Err(Error::UnknownCommand { .. })));
+/// assert!(matches!(s.eval(":bnd"), Err(Error::UnknownCommand { similar, .. }) if similar == ["bind"]));
+/// assert!(matches!(s.eval(":s"), Ok(None)));
+/// assert!(matches!(s.eval(":st"), Err(Error::AmbiguousCommand { .. })));
+///
+/// s.eval(":limit 1").unwrap();
+/// match s.eval("(\\x.x x x) (\\x.x x x)") {
+///     Err(Error::Limit(o)) => assert_eq!(o.status, Status::Limit),
+///     _ => unreachable!(),
+/// }
+/// ```
+#[derive(Debug)]
+pub enum Error {
+    /// Characters that do not form a token
+    Lex(ParseError),
+    /// Tokens that do not form an expression or a program
+    Parse(ParseError),
+    /// Command that does not exist, with the names of those spelled
+    /// closest to it
+    UnknownCommand { name: String, similar: Vec<String> },
+    /// Abbreviation starting the names of several commands
+    AmbiguousCommand {
+        name: String,
+        candidates: Vec<String>,
+    },
+    /// Argument of a command missing or malformed
+    BadArgument(String),
+    /// File that cannot be read or written
+    Io { name: String, err: io::Error },
+    /// Include stack from a file to a file including it again
+    ImportCycle(Vec<PathBuf>),
+    /// Reduction stopped at the limit of steps, with the last term
+    /// reached
+    Limit(Outcome),
+}
+
+impl From<ParseError> for Error {
+    fn from(e: ParseError) -> Self {
+        if e.lexical {
+            Error::Lex(e)
+        } else {
+            Error::Parse(e)
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Lex(e) | Error::Parse(e) => write!(f, "{}", e),
+            Error::UnknownCommand { name, similar } => {
+                write!(f, "Unrecognized command `{}`", name)?;
+                if !similar.is_empty() {
+                    write!(f, ", similar commands are :{}", similar.join(", :"))?;
+                }
+                Ok(())
+            }
+            Error::AmbiguousCommand { name, candidates } => {
+                let (last, rest) = candidates.split_last().unwrap();
+                write!(
+                    f,
+                    "Ambiguous command `{}`, it could be :{} or :{}",
+                    name,
+                    rest.join(", :"),
+                    last
+                )
+            }
+            Error::BadArgument(msg) => write!(f, "{}", msg),
+            Error::Io { name, err } => write!(f, "File `{}`: {}", name, err),
+            Error::ImportCycle(files) => {
+                let files: Vec<String> = files.iter().map(|f| f.display().to_string()).collect();
+                write!(f, "Import cycle: {}", files.join(" -> "))
+            }
+            Error::Limit(o) => write!(f, "Reduction stopped at the limit of {} steps", o.steps),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Io { err, .. } => Some(err),
+            _ => None,
+        }
+    }
+}
@@ -1,7 +1,19 @@
 #![allow(unused_variables)]
-use crate::Subst;
+use crate::{debruijn::Term, Subst};
 use std::fmt;
+use std::str::FromStr;
 
+/// Lambda expression with named variables
+///
+/// ```
+/// use rewriter::Expr;
+///
+/// // let id = \x.x in id y
+/// let id = Expr::lam("x", Expr::var("x"));
+/// let e = Expr::let_("id", id, Expr::app(Expr::var("id"), Expr::var("y")));
+/// assert_eq!(e.to_string(), "let id = λx.x in id y");
+/// assert!(e.freevar("y"));
+/// ```
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub enum Expr {
     App(Box<Expr>, Box<Expr>),
@@ -30,10 +42,7 @@ impl Expr {
     }
 
     pub fn is_var(&self) -> bool {
-        match *self {
-            Var(_) => true,
-            _ => false,
-        }
+        matches!(*self, Var(_))
     }
 
     // x is a free variable in the expression
@@ -46,86 +55,400 @@ impl Expr {
     //
     // \x.\y.z
     // z is free
+    //
+    // let x = x in x
+    // x is free in the bound expression only
     pub fn freevar(&self, x: &str) -> bool {
         match self {
             App(e1, e2) => e1.freevar(x) || e2.freevar(x),
             Lam(y, e) if y != x => e.freevar(x),
-            Let(y, e1, e2) if y != x => e1.freevar(x) || e2.freevar(x),
+            Let(y, e1, e2) => e1.freevar(x) || (y != x && e2.freevar(x)),
             Var(y) if y == x => true,
             _ => false,
         }
     }
+
+    // Free variables of the expression, in order of first occurrence
+    //
+    // \x.f x (g y) x
+    // f g y
+    pub fn freevars(&self) -> Vec<&str> {
+        fn go<'a>(e: &'a Expr, bound: &mut Vec<&'a str>, acc: &mut Vec<&'a str>) {
+            match e {
+                App(e1, e2) => {
+                    go(e1, bound, acc);
+                    go(e2, bound, acc);
+                }
+                Lam(x, e) => {
+                    bound.push(x);
+                    go(e, bound, acc);
+                    bound.pop();
+                }
+                Let(x, e1, e2) => {
+                    go(e1, bound, acc);
+                    bound.push(x);
+                    go(e2, bound, acc);
+                    bound.pop();
+                }
+                Var(x) => {
+                    if !bound.contains(&x.as_str()) && !acc.contains(&x.as_str()) {
+                        acc.push(x)
+                    }
+                }
+            }
+        }
+
+        let mut acc = vec![];
+        go(self, &mut vec![], &mut acc);
+        acc
+    }
+
+    // Number of nodes in the expression
+    pub fn size(&self) -> usize {
+        match self {
+            App(e1, e2) | Let(_, e1, e2) => 1 + e1.size() + e2.size(),
+            Lam(_, e) => 1 + e.size(),
+            Var(_) => 1,
+        }
+    }
+
+    // Equal up to renaming of bound variables
+    //
+    // \x.x and \y.y are alpha equivalent
+    // \x.y and \y.y are not
+    pub fn alpha_eq(&self, other: &Expr) -> bool {
+        Term::from(self) == Term::from(other)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Strategy {
+    Normal,
+    Applicative,
+    CallByName,
+    CallByValue,
+    Head,
+    WeakHead,
+}
+
+impl Strategy {
+    pub const ALL: [Strategy; 6] = [
+        Strategy::Normal,
+        Strategy::Applicative,
+        Strategy::CallByName,
+        Strategy::CallByValue,
+        Strategy::Head,
+        Strategy::WeakHead,
+    ];
+
+    // Reduces inside the body of abstractions
+    fn lambda(self) -> bool {
+        matches!(
+            self,
+            Strategy::Normal | Strategy::Applicative | Strategy::Head
+        )
+    }
+
+    // Reduces function and argument before contracting a redex
+    fn strict(self) -> bool {
+        matches!(self, Strategy::Applicative | Strategy::CallByValue)
+    }
+
+    // Reduces the argument once the function is stuck
+    fn args(self) -> bool {
+        matches!(self, Strategy::Normal)
+    }
+}
+
+impl fmt::Display for Strategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Strategy::Normal => "normal",
+            Strategy::Applicative => "applicative",
+            Strategy::CallByName => "cbn",
+            Strategy::CallByValue => "cbv",
+            Strategy::Head => "head",
+            Strategy::WeakHead => "whnf",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl FromStr for Strategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "normal" => Ok(Strategy::Normal),
+            "applicative" => Ok(Strategy::Applicative),
+            "cbn" | "name" => Ok(Strategy::CallByName),
+            "cbv" | "value" => Ok(Strategy::CallByValue),
+            "head" => Ok(Strategy::Head),
+            "whnf" => Ok(Strategy::WeakHead),
+            _ => Err(format!("Unknown strategy `{}`", s)),
+        }
+    }
 }
 
-pub fn reduce(e: Expr, s: &Subst<Expr>) -> Option<Expr> {
+// Kind of a reduction step
+//
+// Beta contracts (\x.e1) e2, Delta unfolds a binding,
+// Let contracts let x = e1 in e2 and Eta contracts \x.f x
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Step {
+    Beta,
+    Delta,
+    Let,
+    Eta,
+}
+
+/// Perform a single reduction step according to the strategy,
+/// None when the expression is in normal form for it
+///
+/// Call by name and weak head reduction take the same steps,
+/// both stop at weak head normal form
+///
+/// Bindings in s are unfolded only in head position, when applied,
+/// when they are the whole term or the body of an abstraction, so
+/// names stay folded until needed
+///
+/// ```
+/// use rewriter::{parse, reduce, Step, Strategy, Subst};
+///
+/// let s = Subst::new().insert("I".into(), parse("\\x.x").unwrap().unwrap());
+/// let mut e = parse("I (I y)").unwrap().unwrap();
+/// let mut steps = vec![];
+/// while let Some((k, t)) = reduce(e.clone(), &s, Strategy::Normal) {
+///     steps.push(k);
+///     e = t;
+/// }
+/// assert_eq!(e.to_string(), "y");
+/// assert_eq!(steps, [Step::Delta, Step::Beta, Step::Delta, Step::Beta]);
+/// ```
+pub fn reduce(e: Expr, s: &Subst<Expr>, st: Strategy) -> Option<(Step, Expr)> {
     match e {
-        App(e1, e2) => {
-            if let Some(e2) = reduce(*e2.clone(), &s) {
-                return Some(App(e1, Box::new(e2)));
+        Var(x) => s.get(&x).map(|e| (Step::Delta, e.clone())),
+        e => step(e, s, st),
+    }
+}
+
+// Reduction step where variables are left as they are
+fn step(e: Expr, s: &Subst<Expr>, st: Strategy) -> Option<(Step, Expr)> {
+    match e {
+        App(e1, e2) if st.strict() => {
+            if let Some((k, e1)) = reduce(*e1.clone(), s, st) {
+                return Some((k, App(Box::new(e1), e2)));
             }
-            if let Some(e1) = reduce(*e1.clone(), &s) {
-                return Some(App(Box::new(e1), e2));
+            if let Some((k, e2)) = step(*e2.clone(), s, st) {
+                return Some((k, App(e1, Box::new(e2))));
             }
             if let Lam(x, e1) = *e1 {
-                let s = s.extend(x, *e2);
-                Some(s.apply(*e1))
+                Some((Step::Beta, Subst::new().extend(x, *e2).apply(*e1)))
             } else {
                 None
             }
         }
+        App(e1, e2) => {
+            if let Lam(x, e1) = *e1 {
+                return Some((Step::Beta, Subst::new().extend(x, *e2).apply(*e1)));
+            }
+            if let Some((k, e1)) = reduce(*e1.clone(), s, st) {
+                return Some((k, App(Box::new(e1), e2)));
+            }
+            if st.args() {
+                if let Some((k, e2)) = step(*e2.clone(), s, st) {
+                    return Some((k, App(e1, Box::new(e2))));
+                }
+            }
+            None
+        }
         Lam(x, e) => {
-            // FIXME: Eta conversion should be performed separately
-            //if let Some(e) = reduce(*e.clone(), &s) {
-            //    return Some(Lam(x, Box::new(e)));
-            //}
-
-            //if let App(e, y) = *e {
-            //    if let Var(y) = *y {
-            //        if *x == y && !e.freevar(&x) {
-            //            return Some(*e);
-            //        }
-            //    }
-            //}
+            if st.lambda() {
+                // The body is in head position too
+                let (x, e) = binder(x, *e, s);
+                if let Some((k, e)) = reduce(e, s, st) {
+                    return Some((k, Lam(x, Box::new(e))));
+                }
+            }
             None
         }
         Let(x, e1, e2) => {
-            if let Some(e1) = reduce(*e1.clone(), &s) {
-                return Some(Let(x, Box::new(e1), e2.clone()));
+            if st.strict() {
+                if let Some((k, e1)) = step(*e1.clone(), s, st) {
+                    return Some((k, Let(x, Box::new(e1), e2)));
+                }
+
+                if st.lambda() {
+                    let (x, e2) = binder(x.clone(), *e2.clone(), s);
+                    if let Some((k, e2)) = step(e2, s, st) {
+                        return Some((k, Let(x, e1, Box::new(e2))));
+                    }
+                }
             }
 
-            if let Some(e2) = reduce(*e2.clone(), &s) {
-                return Some(Let(x, e1.clone(), Box::new(e2)));
+            Some((Step::Let, Subst::new().extend(x, *e1).apply(*e2)))
+        }
+        Var(_) => None,
+    }
+}
+
+// Binder x of e, renamed when a binding has the same name or has x
+// free, so that bindings unfolded in e neither see it instead of the
+// binding nor have their free variables captured by it
+//
+// :bind g \x.x
+// \g.f g
+// \g'.f g'
+//
+// :bind F \x.z
+// \z.F
+// \z'.F
+fn binder(x: String, e: Expr, s: &Subst<Expr>) -> (String, Expr) {
+    let taken = |y: &str| s.get(y).is_some() || s.mentions(y);
+    if !taken(&x) {
+        return (x, e);
+    }
+
+    let mut y = x.clone();
+    loop {
+        y.push('\'');
+        if !taken(&y) && !e.freevar(&y) {
+            break;
+        }
+    }
+    let e = Subst::new().extend(x, Expr::var(&y)).apply(e);
+    (y, e)
+}
+
+// Perform a single eta reduction, leftmost outermost
+//
+// \x.f x
+// f, if x is not free in f
+pub fn eta(e: Expr) -> Option<Expr> {
+    match e {
+        App(e1, e2) => {
+            if let Some(e1) = eta(*e1.clone()) {
+                return Some(App(Box::new(e1), e2));
             }
+            eta(*e2).map(|e2| App(e1, Box::new(e2)))
+        }
+        Lam(x, e) => {
+            if let App(f, y) = &*e {
+                if let Var(y) = &**y {
+                    if *y == x && !f.freevar(&x) {
+                        return Some(*f.clone());
+                    }
+                }
+            }
+            eta(*e).map(|e| Lam(x, Box::new(e)))
+        }
+        Let(x, e1, e2) => {
+            if let Some(e1) = eta(*e1.clone()) {
+                return Some(Let(x, Box::new(e1), e2));
+            }
+            eta(*e2).map(|e2| Let(x, e1, Box::new(e2)))
+        }
+        Var(_) => None,
+    }
+}
 
-            let s = s.extend(x, *e1);
-            Some(s.apply(*e2))
+// Replace the subterms alpha equivalent to a binding by its name,
+// outermost first, the first binding defined winning
+//
+// :bind I \x.x
+// \f.f (\y.y)
+// \f.f I
+pub fn fold(e: Expr, bind: &[(String, Expr)]) -> Expr {
+    fn go(e: Expr, bind: &[(String, Term)], bound: &mut Vec<String>) -> Expr {
+        if !e.is_var() {
+            // A variable bound outside the subterm is not the free
+            // variable of the same name in the binding
+            let t = Term::from(&e);
+            if !e.freevars().iter().any(|x| bound.iter().any(|y| x == y)) {
+                if let Some((x, _)) = bind.iter().find(|(_, u)| *u == t) {
+                    return Expr::var(x);
+                }
+            }
         }
-        Var(x) => {
-            let e = s.apply(Var(x.clone()));
-            match e {
-                Var(y) if x == y => None,
-                _ => Some(e),
+
+        match e {
+            App(e1, e2) => Expr::app(go(*e1, bind, bound), go(*e2, bind, bound)),
+            Lam(x, e) => {
+                bound.push(x.clone());
+                let e = go(*e, bind, bound);
+                bound.pop();
+                Lam(x, Box::new(e))
+            }
+            Let(x, e1, e2) => {
+                let e1 = go(*e1, bind, bound);
+                bound.push(x.clone());
+                let e2 = go(*e2, bind, bound);
+                bound.pop();
+                Let(x, Box::new(e1), Box::new(e2))
             }
+            Var(x) => Var(x),
         }
     }
+
+    let bind: Vec<(String, Term)> = bind
+        .iter()
+        .filter(|(_, e)| !e.is_var())
+        .map(|(x, e)| (x.clone(), Term::from(e)))
+        .collect();
+    go(e, &bind, &mut vec![])
+}
+
+// Variables are printed as they are, other expressions in parentheses
+fn atom(e: &Expr, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    if e.is_var() {
+        fmt::Display::fmt(e, f)
+    } else {
+        write!(f, "(")?;
+        fmt::Display::fmt(e, f)?;
+        write!(f, ")")
+    }
 }
 
+// Binders of nested abstractions and their body
+//
+// \x.\y.\z.e
+// [x, y, z], e
+fn abstraction(mut e: &Expr) -> (Vec<&str>, &Expr) {
+    let mut xs = vec![];
+    while let Lam(x, e1) = e {
+        xs.push(x.as_str());
+        e = e1;
+    }
+    (xs, e)
+}
+
+// The alternate form {:#} prints nested abstractions compactly
+//
+// λx.λy.λz.e
+// λx y z.e
 impl fmt::Display for Expr {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             App(e1, e2) => {
-                let v1 = e1.is_var();
-                let v2 = e2.is_var();
-                write!(
-                    f,
-                    "{}{}{} {}{}{}",
-                    if !v1 { "(" } else { "" },
-                    e1,
-                    if !v1 { ")" } else { "" },
-                    if !v2 { "(" } else { "" },
-                    e2,
-                    if !v2 { ")" } else { "" }
-                )
+                atom(e1, f)?;
+                write!(f, " ")?;
+                atom(e2, f)
+            }
+            Lam(..) if f.alternate() => {
+                let (xs, e) = abstraction(self);
+                write!(f, "λ{}.", xs.join(" "))?;
+                fmt::Display::fmt(e, f)
+            }
+            Let(x, e1, e2) if f.alternate() => {
+                let (xs, e1) = abstraction(e1);
+                write!(f, "let {}", x)?;
+                for x in xs {
+                    write!(f, " {}", x)?;
+                }
+                write!(f, " = ")?;
+                fmt::Display::fmt(e1, f)?;
+                write!(f, " in ")?;
+                fmt::Display::fmt(e2, f)
             }
             Lam(x, e) => write!(f, "λ{}.{}", x, e),
             Let(x, e1, e2) => write!(f, "let {} = {} in {}", x, e1, e2),
@@ -133,3 +456,100 @@ impl fmt::Display for Expr {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+
+    fn subst(bind: &[(&str, &str)]) -> Subst<Expr> {
+        bind.iter().fold(Subst::new(), |s, (x, e)| {
+            s.insert(x.to_string(), parse(e).unwrap().unwrap())
+        })
+    }
+
+    // Term reached when no step applies, with the kinds of the steps
+    fn normal(src: &str, s: &Subst<Expr>, st: Strategy) -> (String, Vec<Step>) {
+        let mut e = parse(src).unwrap().unwrap();
+        let mut steps = vec![];
+        while let Some((k, t)) = reduce(e.clone(), s, st) {
+            steps.push(k);
+            e = t;
+        }
+        (e.to_string(), steps)
+    }
+
+    #[test]
+    fn whole_term_binding_unfolds() {
+        let s = subst(&[("I", "\\x.x")]);
+        assert_eq!(normal("I", &s, Strategy::Normal).0, "λx.x");
+    }
+
+    #[test]
+    fn applied_binding_unfolds() {
+        let s = subst(&[("I", "\\x.x")]);
+        let (e, steps) = normal("I y", &s, Strategy::Normal);
+        assert_eq!(e, "y");
+        assert_eq!(steps, [Step::Delta, Step::Beta]);
+    }
+
+    #[test]
+    fn argument_binding_stays_folded() {
+        let s = subst(&[("I", "\\x.x")]);
+        assert_eq!(normal("f I", &s, Strategy::Normal).0, "f I");
+    }
+
+    #[test]
+    fn binding_in_body_unfolds() {
+        let s = subst(&[("I", "\\x.x"), ("K", "\\x y.x")]);
+        assert_eq!(normal("K I", &s, Strategy::Normal).0, "λy.λx.x");
+        assert_eq!(normal("K I", &s, Strategy::Head).0, "λy.λx.x");
+        assert_eq!(normal("K I", &s, Strategy::CallByName).0, "λy.I");
+    }
+
+    #[test]
+    fn unfolding_does_not_capture() {
+        let s = subst(&[("F", "\\x.z")]);
+        assert_eq!(normal("\\z.F", &s, Strategy::Normal).0, "λz'.λx.z");
+    }
+
+    #[test]
+    fn binder_named_like_binding_is_renamed() {
+        let s = subst(&[("g", "\\x.x")]);
+        assert_eq!(normal("\\g.(\\y.y) g", &s, Strategy::Normal).0, "λg'.g'");
+    }
+
+    // Term after a single step, none if it is in normal form
+    fn once(src: &str, st: Strategy) -> Option<String> {
+        let e = parse(src).unwrap().unwrap();
+        reduce(e, &Subst::new(), st).map(|(_, e)| e.to_string())
+    }
+
+    #[test]
+    fn strict_strategies_reduce_arguments_first() {
+        let src = "(\\x.z) ((\\x.x x) (\\x.x x))";
+        assert_eq!(normal(src, &Subst::new(), Strategy::Normal).0, "z");
+        assert_eq!(normal(src, &Subst::new(), Strategy::CallByName).0, "z");
+        // The argument steps to itself and the redex is never contracted
+        let same = Some("(λx.z) ((λx.x x) (λx.x x))");
+        assert_eq!(once(src, Strategy::CallByValue).as_deref(), same);
+        assert_eq!(once(src, Strategy::Applicative).as_deref(), same);
+    }
+
+    #[test]
+    fn head_strategy_reduces_under_lambdas() {
+        let src = "\\x.(\\y.y) x";
+        assert_eq!(once(src, Strategy::Head).as_deref(), Some("λx.x"));
+        assert_eq!(once(src, Strategy::Normal).as_deref(), Some("λx.x"));
+        assert_eq!(once(src, Strategy::CallByName), None);
+        assert_eq!(once(src, Strategy::WeakHead), None);
+    }
+
+    #[test]
+    fn normal_strategy_reduces_arguments_of_variables() {
+        let src = "x ((\\y.y) z)";
+        assert_eq!(once(src, Strategy::Normal).as_deref(), Some("x z"));
+        assert_eq!(once(src, Strategy::Head), None);
+        assert_eq!(once(src, Strategy::CallByName), None);
+    }
+}
@@ -0,0 +1,66 @@
+//! Interruption of long reductions and file loads
+//!
+//! The flag is set by SIGINT once [`install`] is called, or by
+//! [`raise`], and checked before each step. A session stops at the next
+//! check and leaves the flag for the caller to [`clear`].
+//!
+//! ```
+//! use rewriter::{interrupt, Session, Status};
+//!
+//! let mut s = Session::new();
+//! interrupt::raise();
+//! let o = s.eval("(\\x.x) y").unwrap().unwrap();
+//! assert!(interrupt::pending());
+//! interrupt::clear();
+//! assert_eq!(o.status, Status::Interrupted);
+//! ```
+
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(test)]
+use std::sync::{Mutex, MutexGuard};
+
+// Set by SIGINT, checked while reducing and loading files
+static PENDING: AtomicBool = AtomicBool::new(false);
+
+/// Whether an interruption was requested since the last [`clear`]
+pub fn pending() -> bool {
+    PENDING.load(Ordering::Relaxed)
+}
+
+/// Forget the requested interruption, before running the next input
+pub fn clear() {
+    PENDING.store(false, Ordering::Relaxed)
+}
+
+/// Request an interruption, as SIGINT does once handled
+pub fn raise() {
+    PENDING.store(true, Ordering::Relaxed)
+}
+
+// Held by the tests that reduce, the flag being shared by the threads
+// running them
+#[cfg(test)]
+pub(crate) fn lock() -> MutexGuard<'static, ()> {
+    static LOCK: Mutex<()> = Mutex::new(());
+    LOCK.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Handle SIGINT by requesting an interruption instead of exiting,
+/// nothing is done on platforms without signals
+#[cfg(unix)]
+pub fn install() {
+    const SIGINT: i32 = 2;
+
+    extern "C" {
+        fn signal(signum: i32, handler: extern "C" fn(i32)) -> usize;
+    }
+
+    extern "C" fn handler(_: i32) {
+        raise()
+    }
+
+    unsafe { signal(SIGINT, handler) };
+}
+
+#[cfg(not(unix))]
+pub fn install() {}
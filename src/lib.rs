@@ -0,0 +1,574 @@
+//! Untyped lambda calculus rewriter
+//!
+//! Expressions are parsed with [`parse()`], reduced step by step with
+//! [`reduce`] under a [`Strategy`], and a [`Session`] interprets the
+//! lines of the interactive prompt, commands included.
+//!
+//! ```
+//! use rewriter::{parse, reduce, Step, Strategy, Subst};
+//!
+//! let e = parse("(\\x.x) y").unwrap().unwrap();
+//! let (k, e) = reduce(e, &Subst::new(), Strategy::Normal).unwrap();
+//! assert_eq!(k, Step::Beta);
+//! assert_eq!(e.to_string(), "y");
+//! ```
+
+// Print a line of results or diagnostics to the output of a config
+macro_rules! out {
+    ($cfg:expr, $($arg:tt)*) => {{
+        let line = format!($($arg)*);
+        $cfg.out.print(&line)
+    }};
+}
+
+macro_rules! err {
+    ($cfg:expr, $($arg:tt)*) => {{
+        let line = format!($($arg)*);
+        $cfg.out.error(&line)
+    }};
+}
+
+mod church;
+mod cmd;
+mod debruijn;
+mod error;
+mod expr;
+pub mod interrupt;
+mod output;
+mod parse;
+mod prelude;
+mod split;
+mod stats;
+mod subst;
+
+pub use cmd::{Args, Command, CommandRegistry, Fun, Kind, Param, Value};
+pub use error::Error;
+pub use expr::{reduce, Expr, Step, Strategy};
+pub use output::{Buffer, Output, Stdio};
+pub use parse::{incomplete, join, parse, parse_program, Decl, ParseError, Span};
+use stats::Stats;
+pub use stats::Status;
+use std::collections::HashSet;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Instant;
+pub use subst::Subst;
+
+/// Settings and bindings of a session, changed by commands
+pub struct Config {
+    limit: usize,
+    strategy: Strategy,
+    eta: bool,
+    compact: bool,
+    fold: bool,
+    stats: bool,
+    trace: Trace,
+    subst: Subst<Expr>,
+    echo: bool,
+    // Include stack, the file being loaded last
+    files: Vec<PathBuf>,
+    // Every file loaded so far
+    loaded: HashSet<PathBuf>,
+    line: usize,
+    // Name of the input when not reading from a file
+    input: &'static str,
+    prompt: bool,
+    // Some input failed to parse or hit the limit
+    failed: bool,
+    // Bindings in definition order
+    bind: Vec<(String, Expr)>,
+    out: Box<dyn Output>,
+}
+
+// How much of the reduction sequence is printed
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Trace {
+    None,
+    Final,
+    All,
+    Every(usize),
+}
+
+impl Trace {
+    // Whether the term after i steps is printed
+    fn shows(self, i: usize) -> bool {
+        match self {
+            Trace::None | Trace::Final => false,
+            Trace::All => true,
+            Trace::Every(n) => i.is_multiple_of(n),
+        }
+    }
+}
+
+impl fmt::Display for Trace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Trace::None => write!(f, "none"),
+            Trace::Final => write!(f, "final"),
+            Trace::All => write!(f, "all"),
+            Trace::Every(n) => write!(f, "every {}", n),
+        }
+    }
+}
+
+impl FromStr for Trace {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut it = s.split_whitespace();
+        match (it.next(), it.next(), it.next()) {
+            (Some("none"), None, _) => Ok(Trace::None),
+            (Some("final"), None, _) => Ok(Trace::Final),
+            (Some("all"), None, _) => Ok(Trace::All),
+            (Some("every"), Some(n), None) => match n.parse::<usize>() {
+                Ok(0) => Err(String::from("Expected positive number but got `0`")),
+                Ok(n) => Ok(Trace::Every(n)),
+                Err(_) => Err(format!("Expected number but got `{}`", n)),
+            },
+            _ => Err(format!(
+                "Expected none, final, all or every N but got `{}`",
+                s
+            )),
+        }
+    }
+}
+
+impl Config {
+    /// Format an expression according to the display settings
+    pub fn show(&self, e: &Expr) -> String {
+        if self.compact {
+            format!("{:#}", e)
+        } else {
+            format!("{}", e)
+        }
+    }
+
+    /// Where commands print their results and diagnostics
+    pub fn output(&mut self) -> &mut dyn Output {
+        self.out.as_mut()
+    }
+
+    /// Bindings in definition order
+    pub fn bindings(&self) -> &[(String, Expr)] {
+        &self.bind
+    }
+
+    // Print an error in src, the current line of the file being loaded
+    // or of the standard input
+    fn report(&mut self, src: &str, e: &Error) {
+        self.failed = true;
+        let origin = match self.files.last() {
+            Some(path) => path.display().to_string(),
+            None => String::from(self.input),
+        };
+        match e {
+            Error::Lex(e) | Error::Parse(e) => err!(self, "{}", e.render(src, &origin, self.line)),
+            e if self.files.is_empty() => err!(self, "{}.", e),
+            e => err!(self, "{}:{}: {}.", origin, self.line, e),
+        }
+    }
+}
+
+/// Result of reducing an expression
+///
+/// ```
+/// use rewriter::{Session, Status};
+///
+/// let mut s = Session::new();
+/// let o = s.eval("(\\x.x x) (\\x.x x)").unwrap().unwrap();
+/// assert_eq!((o.steps, o.status), (0, Status::Cycle));
+/// assert_eq!(o.final_term.to_string(), "(λx.x x) (λx.x x)");
+/// ```
+#[derive(Debug, Clone)]
+pub struct Outcome {
+    /// Number of reduction steps taken
+    pub steps: usize,
+    /// Last term reached, a normal form unless the status says otherwise
+    pub final_term: Expr,
+    pub status: Status,
+}
+
+// Commands (prefixed by :) or expressions, the outcome of the latter
+//
+// Errors are reported before being returned
+fn interpret(
+    cmds: &CommandRegistry,
+    cfg: &mut Config,
+    line: &str,
+) -> Result<Option<Outcome>, Error> {
+    let res = run(cmds, cfg, line);
+    if let Err(e) = &res {
+        cfg.report(line, e);
+    }
+    res
+}
+
+fn run(cmds: &CommandRegistry, cfg: &mut Config, line: &str) -> Result<Option<Outcome>, Error> {
+    if line.starts_with(':') {
+        cmds.run(cfg, line)?;
+        return Ok(None);
+    }
+
+    match parse::parse(line) {
+        Some(Ok(e)) => {
+            let outcome = evaluate(cfg, e);
+            if outcome.status == Status::Limit {
+                return Err(Error::Limit(outcome));
+            }
+            Ok(Some(outcome))
+        }
+        Some(Err(e)) => Err(e.into()),
+        None => {
+            if cfg.files.is_empty() {
+                if cfg.prompt {
+                    out!(cfg, "");
+                }
+            } else if cfg.echo {
+                out!(cfg, "")
+            }
+            Ok(None)
+        }
+    }
+}
+
+// Reduce e, printing the sequence according to the settings
+fn evaluate(cfg: &mut Config, mut e: Expr) -> Outcome {
+    if cfg.trace.shows(0) {
+        out!(cfg, "{}", cfg.show(&e))
+    }
+    let start = Instant::now();
+    let mut stats = Stats::new(&e);
+    let status = loop {
+        let step = expr::reduce(e.clone(), &cfg.subst, cfg.strategy).or_else(|| {
+            cfg.eta
+                .then(|| expr::eta(e.clone()).map(|t| (Step::Eta, t)))
+                .flatten()
+        });
+
+        if interrupt::pending() {
+            break Status::Interrupted;
+        }
+
+        match step {
+            None => break Status::Normal,
+            Some((_, t)) if t.alpha_eq(&e) => break Status::Cycle,
+            Some(_) if stats.steps() >= cfg.limit => break Status::Limit,
+            Some((k, t)) => {
+                e = t;
+                stats.record(k, &e);
+                if cfg.trace.shows(stats.steps()) {
+                    out!(cfg, "{}", cfg.show(&e))
+                }
+            }
+        }
+    };
+    stats.time = start.elapsed();
+    stats.status = Some(status);
+    if status == Status::Limit || status == Status::Interrupted {
+        cfg.failed = true;
+    }
+
+    // The last term reached is shown when interrupted, whatever the trace
+    if cfg.trace != Trace::None || status == Status::Interrupted {
+        if !cfg.trace.shows(stats.steps()) {
+            out!(cfg, "{}", cfg.show(&e))
+        }
+        if cfg.fold {
+            let folded = expr::fold(e.clone(), &cfg.bind);
+            if folded != e {
+                out!(cfg, "= {}", cfg.show(&folded))
+            }
+        }
+        if let Some(lit) = church::literal(&e) {
+            out!(cfg, "= {}", lit)
+        }
+    }
+    if cfg.stats {
+        out!(cfg, "-- {}", stats)
+    } else if cfg.trace == Trace::Final {
+        out!(cfg, "-- {} steps", stats.steps())
+    }
+
+    Outcome {
+        steps: stats.steps(),
+        final_term: e,
+        status,
+    }
+}
+
+/// Interpreter of the lines of the prompt and of script files
+///
+/// ```
+/// use rewriter::Session;
+///
+/// let mut s = Session::new();
+/// s.eval(":bind I \\x.x").unwrap();
+/// s.eval("I I").unwrap();
+/// assert!(!s.failed());
+/// ```
+pub struct Session {
+    cmds: CommandRegistry,
+    cfg: Config,
+}
+
+impl Session {
+    /// Session with the default settings, no bindings and the
+    /// builtin commands
+    pub fn new() -> Self {
+        Session {
+            cmds: CommandRegistry::builtin(),
+            cfg: Config {
+                subst: Subst::new(),
+                limit: 100usize,
+                strategy: Strategy::Normal,
+                eta: false,
+                compact: false,
+                fold: true,
+                stats: true,
+                trace: Trace::All,
+                echo: false,
+                files: vec![],
+                loaded: HashSet::new(),
+                line: 0,
+                input: "<stdin>",
+                prompt: true,
+                failed: false,
+                bind: vec![],
+                out: Box::new(Stdio),
+            },
+        }
+    }
+
+    /// Session printing to out instead of the standard output
+    pub fn with_output(out: Box<dyn Output>) -> Self {
+        let mut s = Session::new();
+        s.cfg.out = out;
+        s
+    }
+
+    /// Interpret a command or an expression, giving the outcome of the
+    /// latter
+    ///
+    /// Errors are also printed to the output
+    ///
+    /// ```
+    /// use rewriter::{Session, Status};
+    ///
+    /// let mut s = Session::new();
+    /// assert!(s.eval(":bind I \\x.x").unwrap().is_none());
+    /// let o = s.eval("I y").unwrap().unwrap();
+    /// assert_eq!(o.final_term.to_string(), "y");
+    /// assert_eq!((o.steps, o.status), (2, Status::Normal));
+    /// assert!(s.eval("\\x x").is_err());
+    /// ```
+    pub fn eval(&mut self, line: &str) -> Result<Option<Outcome>, Error> {
+        interpret(&self.cmds, &mut self.cfg, line)
+    }
+
+    /// Reduce an expression with the bindings and settings of the
+    /// session
+    ///
+    /// Unlike [`Session::eval`], reaching the limit is not an error
+    ///
+    /// ```
+    /// use rewriter::{parse, Session, Status};
+    ///
+    /// let mut s = Session::new();
+    /// s.eval(":bind K \\x y.x").unwrap();
+    /// let o = s.reduce(parse("K a b").unwrap().unwrap());
+    /// assert_eq!(o.final_term.to_string(), "a");
+    ///
+    /// s.eval(":limit 1").unwrap();
+    /// let o = s.reduce(parse("K a b").unwrap().unwrap());
+    /// assert_eq!((o.steps, o.status), (1, Status::Limit));
+    /// ```
+    pub fn reduce(&mut self, e: Expr) -> Outcome {
+        evaluate(&mut self.cfg, e)
+    }
+
+    /// Interpret each line of a script file
+    ///
+    /// Errors in the file are printed as they happen, those preventing
+    /// it from being read are also returned
+    ///
+    /// ```no_run
+    /// let mut s = rewriter::Session::new();
+    /// s.load("combinator").unwrap();
+    /// s.eval("S K K").unwrap();
+    /// ```
+    pub fn load(&mut self, name: &str) -> Result<(), Error> {
+        let res = cmd::load(&self.cmds, &mut self.cfg, Path::new(name));
+        if let Err(e) = &res {
+            self.cfg.report("", e);
+        }
+        res
+    }
+
+    /// Commands run by lines starting with :
+    pub fn commands(&self) -> &CommandRegistry {
+        &self.cmds
+    }
+
+    /// Commands, to register those of the embedding program
+    pub fn commands_mut(&mut self) -> &mut CommandRegistry {
+        &mut self.cmds
+    }
+
+    /// Name and line number of the input in error messages
+    pub fn set_input(&mut self, input: &'static str, line: usize) {
+        self.cfg.input = input;
+        self.cfg.line = line;
+    }
+
+    pub fn set_limit(&mut self, limit: usize) {
+        self.cfg.limit = limit;
+    }
+
+    pub fn set_strategy(&mut self, strategy: Strategy) {
+        self.cfg.strategy = strategy;
+    }
+
+    /// Whether the input comes from an interactive prompt
+    pub fn set_prompt(&mut self, prompt: bool) {
+        self.cfg.prompt = prompt;
+    }
+
+    pub fn prompt(&self) -> bool {
+        self.cfg.prompt
+    }
+
+    /// Some input failed to parse or hit the limit
+    pub fn failed(&self) -> bool {
+        self.cfg.failed
+    }
+
+    /// Bindings in definition order
+    pub fn bindings(&self) -> &[(String, Expr)] {
+        &self.cfg.bind
+    }
+
+    /// Completions of word, command names after : and binding names
+    /// elsewhere
+    pub fn complete(&self, before: &str, word: &str) -> Vec<String> {
+        let mut words: Vec<String> = if before.trim_start() == ":" {
+            self.cmds
+                .iter()
+                .map(|cmd| &cmd.name)
+                .filter(|name| name.starts_with(word))
+                .cloned()
+                .collect()
+        } else {
+            self.cfg
+                .bind
+                .iter()
+                .map(|(name, _)| name)
+                .filter(|name| name.starts_with(word))
+                .cloned()
+                .collect()
+        };
+        words.sort();
+        words
+    }
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Session::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::sync::MutexGuard;
+
+    // Session printing to the returned buffer, without statistics whose
+    // times vary between runs
+    fn session() -> (Session, Buffer, MutexGuard<'static, ()>) {
+        let lock = interrupt::lock();
+        let buf = Buffer::default();
+        let mut s = Session::with_output(Box::new(buf.clone()));
+        s.eval(":stats off").unwrap();
+        s.eval(":bind I \\x.x").unwrap();
+        s.eval(":bind K \\x y.x").unwrap();
+        buf.clear();
+        (s, buf, lock)
+    }
+
+    #[test]
+    fn trace_prints_every_term() {
+        let (mut s, buf, _lock) = session();
+        s.eval("K I z").unwrap();
+        assert_eq!(
+            buf.lines(),
+            ["(K I) z", "((λx.λy.x) I) z", "(λy.I) z", "I", "λx.x", "= I"]
+        );
+    }
+
+    #[test]
+    fn final_trace_prints_last_term_and_steps() {
+        let (mut s, buf, _lock) = session();
+        s.eval(":trace final").unwrap();
+        s.eval("K I z").unwrap();
+        assert_eq!(buf.lines(), ["λx.x", "= I", "-- 4 steps"]);
+    }
+
+    #[test]
+    fn limited_run_is_an_error() {
+        let (mut s, buf, _lock) = session();
+        s.eval(":trace none").unwrap();
+        s.eval(":limit 1").unwrap();
+        match s.eval("(\\x.x x x) (\\x.x x x)") {
+            Err(Error::Limit(o)) => assert_eq!((o.steps, o.status), (1, Status::Limit)),
+            r => panic!("expected the limit but got {:?}", r),
+        }
+        assert_eq!(buf.errors(), ["Reduction stopped at the limit of 1 steps."]);
+        assert!(s.failed());
+    }
+
+    #[test]
+    fn interrupted_run_shows_last_term() {
+        let (mut s, buf, _lock) = session();
+        s.eval(":trace none").unwrap();
+        interrupt::raise();
+        let o = s.eval("K I z");
+        interrupt::clear();
+        let o = o.unwrap().unwrap();
+        assert_eq!((o.steps, o.status), (0, Status::Interrupted));
+        assert_eq!(buf.lines(), ["(K I) z"]);
+        assert!(s.failed());
+    }
+
+    #[test]
+    fn show_prints_bindings() {
+        let (mut s, buf, _lock) = session();
+        s.eval(":show").unwrap();
+        s.eval(":show K").unwrap();
+        assert_eq!(
+            buf.lines(),
+            ["Bindings:", "I = λx.x", "K = λx.λy.x", "K = λx.λy.x"]
+        );
+        assert!(s.eval(":show S").is_err());
+    }
+
+    #[test]
+    fn deps_prints_each_binding_once() {
+        let (mut s, buf, _lock) = session();
+        s.eval(":bind T K I").unwrap();
+        s.eval(":bind U T K I").unwrap();
+        s.eval(":deps U").unwrap();
+        assert_eq!(buf.lines(), ["U -> T, K, I", "T -> K, I", "K", "I"]);
+    }
+
+    #[test]
+    fn save_writes_bind_commands() {
+        let (mut s, _, _lock) = session();
+        let path = std::env::temp_dir().join(format!("rewriter-save-{}", std::process::id()));
+        s.eval(&format!(":save {}", path.display())).unwrap();
+        let script = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(script, "-- Saved bindings\n:bind I λx.x\n:bind K λx.λy.x\n");
+    }
+}
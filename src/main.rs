@@ -1,85 +1,154 @@
-mod cmd;
-mod expr;
-mod parse;
-mod split;
-mod subst;
-
-use cmd::Command;
-use expr::Expr;
-use std::collections::HashMap;
-use std::io::{self, Write};
-use std::path::PathBuf;
-use subst::Subst;
-
-pub struct Config {
-    limit: usize,
-    subst: Subst<Expr>,
-    echo: bool,
-    file: Option<PathBuf>,
-    bind: HashMap<String, Expr>,
-}
+mod edit;
+
+use edit::Editor;
+use rewriter::{incomplete, interrupt, join, Session, Strategy};
+use std::env;
+use std::io;
+use std::process;
 
-// Commands (prefixed by :) or expressions
-fn interpret(cmds: &[Command], cfg: &mut Config, line: &str) -> bool {
-    if let Some(line) = line.strip_prefix(':') {
-        cmd::command(cmds, cfg, line);
+// Read a line of the standard input without its newline, with the
+// line editor unless the prompt is disabled, None at the end of input
+fn read(editor: &mut Editor, session: &Session, prompt: &str) -> io::Result<Option<String>> {
+    let mut line = String::new();
+    if session.prompt() {
+        line = editor.read_line(prompt, &|before, word| session.complete(before, word))?;
     } else {
-        match parse::parse(&line) {
-            Some(Ok(mut e)) => {
-                println!("{}", e);
-                let mut i = 0;
-                while let Some(t) = expr::reduce(e.clone(), &cfg.subst) {
-                    if t == e || i >= cfg.limit {
-                        break;
-                    }
+        io::stdin().read_line(&mut line)?;
+    }
+
+    if line.is_empty() {
+        return Ok(None);
+    }
+    line.truncate(line.trim_end_matches(['\n', '\r']).len());
+    Ok(Some(line))
+}
+
+const USAGE: &str = "\
+Usage: rewriter [options] [file...]
 
-                    e = t;
-                    i += 1;
-                    println!("{}", e)
+Load each file, then evaluate the expressions given with -e
+or start the interactive prompt.
+
+Options:
+    -e expr             Evaluate an expression or command and exit
+    --limit num         Set the limit of reduction steps
+    --strategy name     Set the reduction strategy
+    --no-prompt         Do not print the prompt, for piped input
+    -h, --help          Display this message";
+
+struct Args {
+    files: Vec<String>,
+    exprs: Vec<String>,
+}
+
+fn args(session: &mut Session) -> Result<Args, String> {
+    let mut args = Args {
+        files: vec![],
+        exprs: vec![],
+    };
+
+    let mut it = env::args().skip(1);
+    while let Some(arg) = it.next() {
+        let mut value = |opt: &str| it.next().ok_or(format!("Option `{}` expects a value", opt));
+        match arg.as_str() {
+            "-e" => args.exprs.push(value(&arg)?),
+            "--limit" => {
+                let n = value(&arg)?;
+                match n.parse::<usize>() {
+                    Ok(n) => session.set_limit(n),
+                    Err(_) => return Err(format!("Expected number but got `{}`", n)),
                 }
             }
-            Some(Err(e)) => eprintln!("{}", e),
-            None => {
-                if cfg.file.is_none() {
-                    // Quit on CTRL+D
-                    println!("");
-                    return line.len() == 0;
-                } else if cfg.echo {
-                    println!("")
-                }
+            "--strategy" => session.set_strategy(value(&arg)?.parse::<Strategy>()?),
+            "--no-prompt" => session.set_prompt(false),
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                process::exit(0)
             }
+            _ if arg.starts_with('-') && arg.len() > 1 => {
+                return Err(format!("Unknown option `{}`", arg))
+            }
+            _ => args.files.push(arg),
         }
     }
-    false
+    Ok(args)
+}
+
+// Exit status of a run stopped by SIGINT
+const INTERRUPTED: i32 = 130;
+
+// Exit if CTRL+C was pressed during a batch run, otherwise forget it
+fn stop_if_interrupted(batch: bool) {
+    if interrupt::pending() {
+        if batch {
+            process::exit(INTERRUPTED)
+        }
+        interrupt::clear();
+    }
 }
 
 fn main() -> Result<(), io::Error> {
-    let mut cfg = Config {
-        subst: Subst::new(),
-        limit: 100usize,
-        echo: false,
-        file: None,
-        bind: HashMap::new(),
+    let mut session = Session::new();
+
+    let args = match args(&mut session) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}.", e);
+            eprintln!("{}", USAGE);
+            process::exit(2)
+        }
     };
 
-    let cmds = [
-        cmd::BIND_COMMAND,
-        cmd::ECHO_COMMAND,
-        cmd::FILE_COMMAND,
-        cmd::HELP_COMMAND,
-        cmd::LIMIT_COMMAND,
-        cmd::SHOW_COMMAND,
-    ];
+    // CTRL+C abandons the current line at the prompt, but stops the
+    // whole run otherwise so that scripts can be killed
+    let batch = !args.exprs.is_empty() || !session.prompt();
+    interrupt::install();
+    for file in &args.files {
+        // Expressions given with -e are not evaluated without the
+        // definitions they rely on
+        if session.load(file).is_err() && !args.exprs.is_empty() {
+            process::exit(1)
+        }
+        stop_if_interrupted(batch);
+    }
 
-    let mut line = String::new();
-    loop {
-        print!(">> ");
-        io::stdout().flush()?;
-        io::stdin().read_line(&mut line)?;
+    if !args.exprs.is_empty() {
+        for (i, e) in args.exprs.iter().enumerate() {
+            session.set_input("<arg>", i + 1);
+            // Errors are reported as they happen
+            let _ = session.eval(e);
+            stop_if_interrupted(true);
+        }
+    } else {
+        let mut editor = Editor::new();
+        let mut n = 0;
+        loop {
+            let Some(mut line) = read(&mut editor, &session, ">> ")? else {
+                // Quit on CTRL+D, an empty line leaves the prompt
+                let _ = session.eval("");
+                break;
+            };
+            n += 1;
+            session.set_input("<stdin>", n);
 
-        if interpret(&cmds, &mut cfg, &line) {
-            return Ok(());
+            while incomplete(&line) {
+                match read(&mut editor, &session, ".. ")? {
+                    Some(next) => {
+                        n += 1;
+                        join(&mut line, &next);
+                    }
+                    None => break,
+                }
+            }
+
+            stop_if_interrupted(batch);
+            let _ = session.eval(&line);
+            stop_if_interrupted(batch);
         }
-        line.clear();
     }
+
+    if session.failed() {
+        process::exit(1)
+    }
+    Ok(())
 }
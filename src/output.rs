@@ -0,0 +1,72 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Destination of what a session prints
+pub trait Output {
+    /// A line of results, terms of the reduction sequence included
+    fn print(&mut self, line: &str);
+
+    /// A line of diagnostics
+    fn error(&mut self, line: &str);
+}
+
+/// Standard output and standard error, as used by the prompt
+pub struct Stdio;
+
+impl Output for Stdio {
+    fn print(&mut self, line: &str) {
+        println!("{}", line)
+    }
+
+    fn error(&mut self, line: &str) {
+        eprintln!("{}", line)
+    }
+}
+
+/// Output kept in memory, shared by the clones of the buffer so that
+/// it can be read while a session writes to it
+///
+/// ```
+/// use rewriter::{Buffer, Session};
+///
+/// let buf = Buffer::default();
+/// let mut s = Session::with_output(Box::new(buf.clone()));
+/// s.eval(":limit").unwrap();
+/// assert_eq!(buf.lines(), ["100"]);
+/// ```
+#[derive(Clone, Default)]
+pub struct Buffer(Rc<RefCell<Lines>>);
+
+#[derive(Default)]
+struct Lines {
+    out: Vec<String>,
+    err: Vec<String>,
+}
+
+impl Buffer {
+    /// Lines of results printed so far
+    pub fn lines(&self) -> Vec<String> {
+        self.0.borrow().out.clone()
+    }
+
+    /// Lines of diagnostics printed so far
+    pub fn errors(&self) -> Vec<String> {
+        self.0.borrow().err.clone()
+    }
+
+    pub fn clear(&self) {
+        let mut lines = self.0.borrow_mut();
+        lines.out.clear();
+        lines.err.clear();
+    }
+}
+
+impl Output for Buffer {
+    fn print(&mut self, line: &str) {
+        self.0.borrow_mut().out.push(line.to_string())
+    }
+
+    fn error(&mut self, line: &str) {
+        self.0.borrow_mut().err.push(line.to_string())
+    }
+}
@@ -1,4 +1,5 @@
-use crate::Expr;
+use crate::{church, Expr};
+use std::fmt;
 use std::iter::Peekable;
 
 #[derive(Debug, Eq, PartialEq)]
@@ -10,104 +11,345 @@ enum Token {
     Let,
     Lam,
     Dot,
+    Semi,
     Eof,
+    Num(usize),
+    Bool(bool),
     Var(String),
 }
 
-type Lexer<'a, I> = (Peekable<I>, Option<Token>);
+// Position of a token in the source
+//
+// start and end are byte offsets, line and col
+// are the 1-based line and column of start
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub col: usize,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ParseError {
+    pub msg: String,
+    pub span: Span,
+    // Raised by the lexer rather than the parser
+    pub lexical: bool,
+}
+
+impl ParseError {
+    // Move the span past the given prefix of the source,
+    // used when the parsed string is a slice of a longer line
+    pub fn shift(mut self, prefix: &str) -> Self {
+        self.span.start += prefix.len();
+        self.span.end += prefix.len();
+        // Only the first line of the slice shares a line with the prefix
+        if self.span.line == 1 {
+            let last = prefix.rsplit('\n').next().unwrap_or("");
+            self.span.col += last.chars().count();
+        }
+        self.span.line += prefix.matches('\n').count();
+        self
+    }
+
+    // Error message with the offending line and a caret under the span
+    //
+    // combinator:5:15: Expected Dot but got Eof
+    //  5 | :bind K \x y x
+    //    |               ^
+    pub fn render(&self, src: &str, origin: &str, line: usize) -> String {
+        let line = line + self.span.line - 1;
+        let text = src.lines().nth(self.span.line - 1).unwrap_or("");
+        let width = src
+            .get(self.span.start..self.span.end)
+            .map_or(0, |s| s.chars().count())
+            .max(1);
+        let num = line.to_string();
+
+        format!(
+            "{}:{}:{}: {}\n {} | {}\n {} | {}{}",
+            origin,
+            line,
+            self.span.col,
+            self.msg,
+            num,
+            text,
+            " ".repeat(num.len()),
+            " ".repeat(self.span.col - 1),
+            "^".repeat(width),
+        )
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.span.line, self.span.col, self.msg)
+    }
+}
+
+struct Lexer<I: Iterator<Item = char>> {
+    it: Peekable<I>,
+    peek: Option<Token>,
+    span: Span,
+    pos: usize,
+    line: usize,
+    col: usize,
+    // End of the last token, where Eof is reported
+    last: (usize, usize, usize),
+}
+
+impl<I: Iterator<Item = char>> Lexer<I> {
+    fn new(it: I) -> Self {
+        Lexer {
+            it: it.peekable(),
+            peek: None,
+            span: Span {
+                start: 0,
+                end: 0,
+                line: 1,
+                col: 1,
+            },
+            pos: 0,
+            line: 1,
+            col: 1,
+            last: (0, 1, 1),
+        }
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.it.next()?;
+        self.pos += c.len_utf8();
+        if c == '\n' {
+            self.line += 1;
+            self.col = 1;
+        } else {
+            self.col += 1;
+        }
+        Some(c)
+    }
+
+    // Start a new token at the current position
+    fn mark(&mut self) {
+        self.span = Span {
+            start: self.pos,
+            end: self.pos,
+            line: self.line,
+            col: self.col,
+        };
+    }
+
+    // Error located at the last token
+    fn error<T>(&self, msg: String) -> Result<T, ParseError> {
+        Err(ParseError {
+            msg,
+            span: self.span,
+            lexical: false,
+        })
+    }
+}
 
 fn backtrack<I: Iterator<Item = char>>(lex: &mut Lexer<I>, t: Token) {
-    debug_assert!(lex.1.is_none());
-    lex.1 = Some(t);
+    debug_assert!(lex.peek.is_none());
+    lex.peek = Some(t);
 }
 
-fn token<I: Iterator<Item = char>>(lex: &mut Lexer<I>) -> Result<Token, String> {
-    if let Some(t) = lex.1.take() {
+fn token<I: Iterator<Item = char>>(lex: &mut Lexer<I>) -> Result<Token, ParseError> {
+    if let Some(t) = lex.peek.take() {
         return Ok(t);
     }
 
-    let it = &mut lex.0;
-    while let Some(&c) = it.peek() {
+    let t = lex_token(lex).map_err(|e| ParseError { lexical: true, ..e });
+    if let Ok(Token::Eof) = t {
+        let (pos, line, col) = lex.last;
+        lex.span = Span {
+            start: pos,
+            end: pos,
+            line,
+            col,
+        };
+    } else {
+        lex.span.end = lex.pos;
+        lex.last = (lex.pos, lex.line, lex.col);
+    }
+    t
+}
+
+fn lex_token<I: Iterator<Item = char>>(lex: &mut Lexer<I>) -> Result<Token, ParseError> {
+    while let Some(&c) = lex.it.peek() {
+        lex.mark();
         match c {
             '(' => {
-                it.next();
+                lex.bump();
                 return Ok(Token::ParL);
             }
             ')' => {
-                it.next();
+                lex.bump();
                 return Ok(Token::ParR);
             }
             '\\' | 'λ' => {
-                it.next();
+                lex.bump();
                 return Ok(Token::Lam);
             }
             '.' => {
-                it.next();
+                lex.bump();
                 return Ok(Token::Dot);
             }
             '=' => {
-                it.next();
+                lex.bump();
                 return Ok(Token::Eq);
             }
+            ';' => {
+                lex.bump();
+                return Ok(Token::Semi);
+            }
             'a'..='z' | 'A'..='Z' => {
                 let mut buf = String::new();
-                while let Some(&c) = it.peek() {
+                while let Some(&c) = lex.it.peek() {
                     match c {
                         'a'..='z' | 'A'..='Z' | '_' => {
                             buf.push(c);
-                            it.next();
+                            lex.bump();
                         }
                         _ => break,
                     }
                 }
 
-                while let Some(&c) = it.peek() {
+                while let Some(&c) = lex.it.peek() {
                     if c == '\'' {
                         buf.push(c);
-                        it.next();
+                        lex.bump();
                     } else {
                         break;
                     }
                 }
+                if lex.it.peek().is_some_and(char::is_ascii_digit) {
+                    return mixed(lex, buf);
+                }
 
-                return Ok(if buf == "let" {
-                    Token::Let
-                } else if buf == "in" {
-                    Token::In
-                } else {
-                    Token::Var(buf)
+                return Ok(match buf.as_str() {
+                    "let" => Token::Let,
+                    "in" => Token::In,
+                    "true" => Token::Bool(true),
+                    "false" => Token::Bool(false),
+                    _ => Token::Var(buf),
                 });
             }
+            '0'..='9' => {
+                let mut buf = String::new();
+                while let Some(&c) = lex.it.peek() {
+                    if c.is_ascii_digit() {
+                        buf.push(c);
+                        lex.bump();
+                    } else {
+                        break;
+                    }
+                }
+                if lex
+                    .it
+                    .peek()
+                    .is_some_and(|&c| c.is_ascii_alphabetic() || c == '_' || c == '\'')
+                {
+                    return mixed(lex, buf);
+                }
+
+                return match buf.parse::<usize>() {
+                    Ok(n) if n <= church::MAX_NUMERAL => Ok(Token::Num(n)),
+                    Ok(_) => {
+                        lex.span.end = lex.pos;
+                        lex.error(format!(
+                            "Numeral {} is larger than the maximum of {}",
+                            buf,
+                            church::MAX_NUMERAL
+                        ))
+                    }
+                    Err(e) => {
+                        lex.span.end = lex.pos;
+                        lex.error(format!("Invalid number {}: {}", buf, e))
+                    }
+                };
+            }
             '-' => {
-                if let Some('-') = it.next() {
-                    while let Some(&c) = it.peek() {
+                if let Some('-') = lex.bump() {
+                    while let Some(&c) = lex.it.peek() {
                         if c == '\n' {
                             break;
                         } else {
-                            it.next();
+                            lex.bump();
                         }
                     }
                 }
             }
             ' ' | '\t' | '\n' => {
-                it.next();
+                lex.bump();
+            }
+            c => {
+                lex.bump();
+                lex.span.end = lex.pos;
+                return lex.error(format!("Unexpected char {:?}", c));
             }
-            c => Err(format!("Unexpected char {:?}", c))?,
         };
     }
     Ok(Token::Eof)
 }
 
-fn expect<I: Iterator<Item = char>>(lex: &mut Lexer<I>, e: Token) -> Result<Token, String> {
+// Error for a word mixing letters and digits, which is neither a name
+// nor a numeral, spanning the whole word
+//
+// x1  3x
+fn mixed<I: Iterator<Item = char>>(
+    lex: &mut Lexer<I>,
+    mut buf: String,
+) -> Result<Token, ParseError> {
+    while let Some(&c) = lex.it.peek() {
+        if c.is_ascii_alphanumeric() || c == '_' || c == '\'' {
+            buf.push(c);
+            lex.bump();
+        } else {
+            break;
+        }
+    }
+    lex.span.end = lex.pos;
+    lex.error(format!(
+        "Invalid name `{}`, names cannot contain digits",
+        buf
+    ))
+}
+
+fn expect<I: Iterator<Item = char>>(lex: &mut Lexer<I>, e: Token) -> Result<Token, ParseError> {
     let t = token(lex)?;
     if t != e {
-        Err(format!("Expected {:?} but got {:?}", e, t))
+        lex.error(format!("Expected {:?} but got {:?}", e, t))
     } else {
         Ok(t)
     }
 }
 
-fn parse_expr<I: Iterator<Item = char>>(lex: &mut Lexer<I>) -> Result<Expr, String> {
+// Binders up to the closing token
+//
+// \x y z.
+// let f x y =
+fn params<I: Iterator<Item = char>>(
+    lex: &mut Lexer<I>,
+    end: Token,
+) -> Result<Vec<String>, ParseError> {
+    let mut xs = vec![];
+    loop {
+        match token(lex)? {
+            Token::Var(x) => xs.push(x),
+            t if t == end => return Ok(xs),
+            t => return lex.error(format!("Expected Var but got {:?}", t)),
+        }
+    }
+}
+
+// \x y z.e
+// \x.\y.\z.e
+fn abstraction(xs: &[String], e: Expr) -> Expr {
+    xs.iter().rev().fold(e, |e, x| Expr::lam(x, e))
+}
+
+fn parse_expr<I: Iterator<Item = char>>(lex: &mut Lexer<I>) -> Result<Expr, ParseError> {
     let mut e1 = match token(lex)? {
         Token::ParL => {
             let e = parse_expr(lex)?;
@@ -115,34 +357,35 @@ fn parse_expr<I: Iterator<Item = char>>(lex: &mut Lexer<I>) -> Result<Expr, Stri
             Ok(e)
         }
         Token::Lam => {
-            let t = token(lex)?;
-            if let Token::Var(x) = t {
-                expect(lex, Token::Dot)?;
-                let e = parse_expr(lex)?;
-                Ok(Expr::Lam(x, Box::new(e)))
+            let xs = params(lex, Token::Dot)?;
+            if xs.is_empty() {
+                lex.error(format!("Expected Var but got {:?}", Token::Dot))
             } else {
-                Err(format!("Expected Var but got {:?}", t))
+                let e = parse_expr(lex)?;
+                Ok(abstraction(&xs, e))
             }
         }
         Token::Let => {
             let t = token(lex)?;
             if let Token::Var(x) = t {
-                expect(lex, Token::Eq)?;
+                let xs = params(lex, Token::Eq)?;
                 let e1 = parse_expr(lex)?;
 
                 expect(lex, Token::In)?;
                 let e2 = parse_expr(lex)?;
-                Ok(Expr::Let(x, Box::new(e1), Box::new(e2)))
+                Ok(Expr::let_(&x, abstraction(&xs, e1), e2))
             } else {
-                Err(format!("Expected Var but got {:?}", t))
+                lex.error(format!("Expected Var but got {:?}", t))
             }
         }
+        Token::Num(n) => Ok(church::numeral(n)),
+        Token::Bool(b) => Ok(church::boolean(b)),
         Token::Var(x) => Ok(Expr::Var(x)),
-        t => Err(format!("Unexpected token {:?}", t)),
+        t => lex.error(format!("Unexpected token {:?}", t)),
     }?;
 
     while let Some(e2) = parse_base(lex) {
-        e1 = Expr::App(Box::new(e1), Box::new(e2?));
+        e1 = Expr::app(e1, e2?);
     }
     Ok(e1)
 }
@@ -157,15 +400,17 @@ macro_rules! trans {
     };
 }
 
-fn parse_base<I: Iterator<Item = char>>(lex: &mut Lexer<I>) -> Option<Result<Expr, String>> {
+fn parse_base<I: Iterator<Item = char>>(lex: &mut Lexer<I>) -> Option<Result<Expr, ParseError>> {
     Some(match trans!(token(lex)) {
         Token::ParL => {
             let e = trans!(parse_expr(lex));
             trans!(expect(lex, Token::ParR));
             Ok(e)
         }
+        Token::Num(n) => Ok(church::numeral(n)),
+        Token::Bool(b) => Ok(church::boolean(b)),
         Token::Var(x) => Ok(Expr::Var(x)),
-        t @ (Token::Dot | Token::Lam) => Err(format!("Unexpected token {:?}", t)),
+        t @ (Token::Dot | Token::Lam) => lex.error(format!("Unexpected token {:?}", t)),
         t => {
             backtrack(lex, t);
             return None;
@@ -173,17 +418,134 @@ fn parse_base<I: Iterator<Item = char>>(lex: &mut Lexer<I>) -> Option<Result<Exp
     })
 }
 
-pub fn parse(s: &str) -> Option<Result<Expr, String>> {
-    let mut lex = (s.chars().peekable(), None);
+/// Top-level definition of a program
+#[derive(Debug, Clone)]
+pub struct Decl {
+    pub name: String,
+    pub expr: Expr,
+    pub span: Span,
+}
+
+/// Sequence of definitions, each terminated by a semicolon, the
+/// parameters on the left becoming abstractions
+///
+/// ```
+/// use rewriter::parse_program;
+///
+/// let decls = parse_program("K = \\x y.x;\nS x y z = x z (y z);").unwrap();
+/// assert_eq!(decls[0].name, "K");
+/// assert_eq!(decls[1].expr.to_string(), "λx.λy.λz.(x z) (y z)");
+/// assert!(parse_program("K = \\x y.x").is_err());
+/// ```
+pub fn parse_program(s: &str) -> Result<Vec<Decl>, ParseError> {
+    let mut lex = Lexer::new(s.chars());
+    let mut decls: Vec<Decl> = vec![];
+    loop {
+        let name = match token(&mut lex)? {
+            Token::Eof => return Ok(decls),
+            Token::Var(x) => x,
+            t => return lex.error(format!("Expected Var but got {:?}", t)),
+        };
+        let span = lex.span;
+
+        if let Some(d) = decls.iter().find(|d| d.name == name) {
+            return lex.error(format!(
+                "Duplicate definition of `{}`, first defined at {}:{}",
+                name, d.span.line, d.span.col
+            ));
+        }
+
+        let xs = params(&mut lex, Token::Eq)?;
+        let e = parse_expr(&mut lex)?;
+        expect(&mut lex, Token::Semi)?;
+        decls.push(Decl {
+            name,
+            expr: abstraction(&xs, e),
+            span,
+        });
+    }
+}
+
+// Input continues on the next line, either explicitly
+// with a trailing \ or because of unbalanced parentheses
+//
+// A \ ending a line is dropped when joining it to the next one,
+// unless it starts an abstraction there, see continuation()
+//
+// :bind theta (\x y.y (x x y)) \
+//     (\x y.y (x x y))
+pub fn incomplete(s: &str) -> bool {
+    // Parentheses and backslashes in comments do not count
+    let code = |line: &str| line.split("--").next().unwrap_or("").to_string();
+    if let Some(last) = s.lines().last() {
+        if code(last).trim_end().ends_with('\\') {
+            return true;
+        }
+    }
+
+    let mut depth = 0;
+    for c in s
+        .lines()
+        .flat_map(|line| code(line).chars().collect::<Vec<_>>())
+    {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            _ => {}
+        }
+    }
+    depth > 0
+}
+
+// Whether the trailing \ of s marks a continuation rather than
+// starting an abstraction, which it does right after ( . = or a name
+//
+// (\
+//     x.x) y
+fn continuation(s: &str) -> bool {
+    match s.strip_suffix('\\') {
+        Some(rest) => !rest
+            .chars()
+            .last()
+            .is_some_and(|c| "(.=_'".contains(c) || c.is_alphanumeric()),
+        None => false,
+    }
+}
+
+// Append the next line of an incomplete input, dropping the trailing \
+// when it marks a continuation
+//
+// Both are lines without their newline, blank lines included so that
+// errors point at the right line
+pub fn join(buf: &mut String, line: &str) {
+    buf.truncate(buf.trim_end_matches([' ', '\t']).len());
+    if continuation(buf) {
+        buf.pop();
+    }
+    buf.push('\n');
+    buf.push_str(line);
+}
+
+/// Parse an expression, None when the input is blank
+///
+/// ```
+/// use rewriter::{parse, Expr};
+///
+/// let e = parse("\\x.x y").unwrap().unwrap();
+/// assert_eq!(e, Expr::lam("x", Expr::app(Expr::var("x"), Expr::var("y"))));
+/// assert!(parse("   ").is_none());
+/// assert!(parse("\\x x").unwrap().is_err());
+/// ```
+pub fn parse(s: &str) -> Option<Result<Expr, ParseError>> {
+    let mut lex = Lexer::new(s.chars());
     match trans!(token(&mut lex)) {
         Token::Eof => None,
         t => {
             backtrack(&mut lex, t);
-            let e = parse_expr(&mut lex);
-            if let Ok(Token::Eof) = token(&mut lex) {
-                Some(e)
-            } else {
-                Some(Err(String::from("Parsing error")))
+            let e = trans!(parse_expr(&mut lex));
+            match trans!(token(&mut lex)) {
+                Token::Eof => Some(Ok(e)),
+                t => Some(lex.error(format!("Unexpected token {:?}", t))),
             }
         }
     }
@@ -0,0 +1,93 @@
+use crate::expr::{self, Strategy};
+use crate::{interrupt, Expr, Subst};
+
+// Definitions of the prelude, in module syntax
+pub const SOURCE: &str = include_str!("prelude.lc");
+
+// Name of the prelude in error messages
+pub const ORIGIN: &str = "<prelude>";
+
+// Steps allowed to reach a normal form when checking examples
+const LIMIT: usize = 10000;
+
+pub struct Example<'a> {
+    pub line: usize,
+    pub expr: &'a str,
+    pub normal: &'a str,
+}
+
+// Examples documented in the prelude by comments like
+//
+// -- add 2 3 => 5
+pub fn examples() -> impl Iterator<Item = Example<'static>> {
+    SOURCE.lines().enumerate().filter_map(|(i, line)| {
+        let (expr, normal) = line.strip_prefix("-- ")?.split_once(" => ")?;
+        Some(Example {
+            line: i + 1,
+            expr: expr.trim(),
+            normal: normal.trim(),
+        })
+    })
+}
+
+// Normal form of e, None when it is not reached within the limit
+pub fn normalize(mut e: Expr, s: &Subst<Expr>) -> Option<Expr> {
+    for _ in 0..LIMIT {
+        if interrupt::pending() {
+            return None;
+        }
+        match expr::reduce(e.clone(), s, Strategy::Normal) {
+            Some((_, t)) => e = t,
+            None => return Some(e),
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+
+    fn prelude() -> (Vec<String>, Subst<Expr>) {
+        let decls = parse::parse_program(SOURCE).unwrap();
+        let names = decls.iter().map(|d| d.name.clone()).collect();
+        let s = decls
+            .into_iter()
+            .fold(Subst::new(), |s, d| s.insert(d.name, d.expr));
+        (names, s)
+    }
+
+    #[test]
+    fn examples_reach_their_normal_form() {
+        let _lock = interrupt::lock();
+        let (_, s) = prelude();
+        for ex in examples() {
+            let normal = |src: &str| {
+                let e = parse::parse(src).unwrap().unwrap();
+                normalize(e, &s).unwrap_or_else(|| panic!("line {}: no normal form", ex.line))
+            };
+            let (e, nf) = (normal(ex.expr), normal(ex.normal));
+            assert!(
+                e.alpha_eq(&nf),
+                "line {}: `{}` expected `{}` but got `{}`",
+                ex.line,
+                ex.expr,
+                ex.normal,
+                e
+            );
+        }
+    }
+
+    #[test]
+    fn every_definition_has_an_example() {
+        let (names, _) = prelude();
+        for x in names {
+            assert!(
+                examples().any(|ex| ex.expr.split(|c: char| !c.is_alphabetic()).any(|w| w == x)),
+                "`{}` has no example",
+                x
+            );
+        }
+    }
+}
@@ -69,6 +69,7 @@ pub fn split_n<'a>(s: &'a str, n: usize, p: fn(u8) -> bool) -> Split<'a> {
     }
 }
 
+#[allow(dead_code)]
 pub fn split<'a>(s: &'a str, p: fn(u8) -> bool) -> Split<'a> {
     Split {
         slice: s.as_bytes(),
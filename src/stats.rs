@@ -0,0 +1,79 @@
+use crate::expr::{Expr, Step};
+use std::fmt;
+use std::time::Duration;
+
+// Why the reduction stopped
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Status {
+    Normal,
+    Limit,
+    Cycle,
+    Interrupted,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Stats {
+    pub beta: usize,
+    pub delta: usize,
+    pub let_: usize,
+    pub eta: usize,
+    pub size: usize,
+    pub time: Duration,
+    pub status: Option<Status>,
+}
+
+impl Stats {
+    pub fn new(e: &Expr) -> Self {
+        Stats {
+            size: e.size(),
+            ..Default::default()
+        }
+    }
+
+    // Account for a step leading to e
+    pub fn record(&mut self, k: Step, e: &Expr) {
+        match k {
+            Step::Beta => self.beta += 1,
+            Step::Delta => self.delta += 1,
+            Step::Let => self.let_ += 1,
+            Step::Eta => self.eta += 1,
+        }
+        self.size = self.size.max(e.size());
+    }
+
+    pub fn steps(&self) -> usize {
+        self.beta + self.delta + self.let_ + self.eta
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Status::Normal => "normal form",
+            Status::Limit => "limit reached",
+            Status::Cycle => "reduces to itself",
+            Status::Interrupted => "interrupted",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+// normal form, 4 steps (3 beta, 1 delta, 0 let, 0 eta), max size 12, 52µs
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(status) = self.status {
+            write!(f, "{}, ", status)?;
+        }
+        write!(
+            f,
+            "{} steps ({} beta, {} delta, {} let, {} eta), max size {}, {:?}",
+            self.steps(),
+            self.beta,
+            self.delta,
+            self.let_,
+            self.eta,
+            self.size,
+            self.time
+        )
+    }
+}
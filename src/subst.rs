@@ -1,14 +1,47 @@
 use crate::{Expr, Expr::*};
 use immutable_map::TreeMap;
 
+/// Finite map from variables to expressions, applied without
+/// capturing free variables
+///
+/// ```
+/// use rewriter::{parse, Expr, Subst};
+///
+/// // [y := x] \x.y
+/// let s = Subst::new().extend("y".into(), Expr::var("x"));
+/// let e = s.apply(parse("\\x.y").unwrap().unwrap());
+/// assert_eq!(e.to_string(), "λx'.x");
+/// ```
 #[derive(Clone)]
 pub struct Subst<T: Clone>(TreeMap<String, T>);
 
+impl Default for Subst<Expr> {
+    fn default() -> Self {
+        Subst::new()
+    }
+}
+
 impl Subst<Expr> {
     pub fn new() -> Self {
         Subst(TreeMap::new())
     }
 
+    // Substitution with the mapping of k replaced, leaving the other
+    // mappings as they are
+    pub fn insert(&self, k: String, v: Expr) -> Self {
+        Subst(self.0.insert(k, v))
+    }
+
+    pub fn get(&self, k: &str) -> Option<&Expr> {
+        self.0.get(k)
+    }
+
+    // Whether x is free in one of the substituted terms
+    pub fn mentions(&self, x: &str) -> bool {
+        self.0.values().any(|v| v.freevar(x))
+    }
+
+    // Composition with the substitution of k by v
     pub fn extend(&self, k: String, v: Expr) -> Self {
         if self.0.is_empty() {
             Subst(self.0.insert(k, v))
@@ -25,9 +58,14 @@ impl Subst<Expr> {
     pub fn apply(&self, e: Expr) -> Expr {
         match e {
             App(e1, e2) => App(Box::new(self.apply(*e1)), Box::new(self.apply(*e2))),
-            Lam(x, e) if !self.0.contains_key(&x) => Lam(x, Box::new(self.apply(*e))),
-            Let(x, e1, e2) if !self.0.contains_key(&x) => {
-                Let(x, Box::new(self.apply(*e1)), Box::new(self.apply(*e2)))
+            Lam(x, e) => {
+                let (x, s) = self.scope(x, &e);
+                Lam(x, Box::new(s.apply(*e)))
+            }
+            Let(x, e1, e2) => {
+                let e1 = self.apply(*e1);
+                let (x, s) = self.scope(x, &e2);
+                Let(x, Box::new(e1), Box::new(s.apply(*e2)))
             }
             Var(x) => {
                 if let Some(e) = self.0.get(&x) {
@@ -36,7 +74,77 @@ impl Subst<Expr> {
                     Var(x)
                 }
             }
-            _ => e,
         }
     }
+
+    // Substitution without the mapping of x, for use under a binder of x
+    pub fn shadow(&self, x: &str) -> Self {
+        match self.0.remove(x) {
+            Some((s, _)) => Subst(s),
+            None => self.clone(),
+        }
+    }
+
+    // Substitution to apply under the binder x whose body is e
+    //
+    // x shadows its own mapping, and if one of the substituted terms
+    // would end up under the binder with x free, x is renamed
+    //
+    // [y := x] \x.y
+    // \x'.x
+    fn scope(&self, x: String, e: &Expr) -> (String, Self) {
+        let s = self.shadow(&x).0;
+
+        let capture = s.iter().any(|(k, v)| v.freevar(&x) && e.freevar(k));
+        if !capture {
+            return (x, Subst(s));
+        }
+
+        let mut y = x.clone();
+        loop {
+            y.push('\'');
+            if !e.freevar(&y) && !s.values().any(|v| v.freevar(&y)) {
+                break;
+            }
+        }
+
+        let s = s.insert(x, Expr::var(&y));
+        (y, Subst(s))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse, reduce, Strategy};
+
+    fn normal(src: &str) -> String {
+        let mut e = parse(src).unwrap().unwrap();
+        while let Some((_, t)) = reduce(e.clone(), &Subst::new(), Strategy::Normal) {
+            e = t;
+        }
+        e.to_string()
+    }
+
+    #[test]
+    fn beta_renames_captured_binder() {
+        assert_eq!(normal("(\\x.\\y.x) y"), "λy'.y");
+    }
+
+    #[test]
+    fn let_renames_captured_binder() {
+        assert_eq!(normal("let x = y in \\y.x"), "λy'.y");
+    }
+
+    #[test]
+    fn renaming_skips_used_names() {
+        assert_eq!(normal("(\\x.\\y.x y') y"), "λy''.y y'");
+    }
+
+    #[test]
+    fn binder_shadows_its_mapping() {
+        let s = Subst::new().extend("x".into(), Expr::var("z"));
+        let e = s.apply(parse("x (\\x.x)").unwrap().unwrap());
+        assert_eq!(e.to_string(), "z (λx.x)");
+    }
 }